@@ -0,0 +1,194 @@
+use crate::dtmf_signals::ButtonFrequency;
+use crate::estimator::estimate_peak;
+use crate::goertzel::FilterBank;
+use crate::noise_floor::{NoiseFloor, STD_SNR_DB};
+use crate::space_command_remote::{RemoteButtonSignal, RemoteProfile, MAX_REMOTE_BUTTONS};
+use crate::window::Window;
+
+/// How many strikes of each button are recorded
+pub const CALIBRATION_STRIKES: usize = 5;
+/// How many blocks the floor is fed before strikes are listened for, about
+/// as long as `NoiseFloor::DEFAULT` takes to settle on the noise
+const SETTLE_BLOCKS: u32 = 100;
+
+/// What one strike of a rod measured as, at its strongest block
+#[derive(Copy, Clone, Debug)]
+struct Strike {
+    /// The estimated peak, in Hz
+    frequency: f32,
+    /// The windowed power at the peak, on the scale of the detector's
+    /// `process_q15_windowed` bank
+    power: f32,
+}
+
+/// Where a calibration run has got to
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CalibrationStep {
+    /// Waiting for strikes of the button at `button` in the profile
+    Listening { button: usize, strikes: usize },
+    /// Every button has been measured
+    Done,
+}
+
+/// A profile with every button's `ButtonFrequency` replaced by what was measured
+#[derive(Clone)]
+pub struct CalibratedRemote {
+    name: &'static str,
+    buttons: [RemoteButtonSignal<'static>; MAX_REMOTE_BUTTONS],
+    len: usize,
+}
+
+impl CalibratedRemote {
+    fn new(profile: &RemoteProfile<'static>) -> CalibratedRemote {
+        assert!(!profile.is_empty() && profile.len() <= MAX_REMOTE_BUTTONS);
+        CalibratedRemote {
+            name: profile.name,
+            //the slots past the profile's buttons are never looked at
+            buttons: core::array::from_fn(|i| profile.buttons()[i.min(profile.len() - 1)].clone()),
+            len: profile.len(),
+        }
+    }
+
+    /// The tuned buttons, to use in place of the profile calibrated from
+    pub fn profile(&self) -> RemoteProfile<'_> {
+        RemoteProfile::new(self.name, &self.buttons[..self.len])
+    }
+}
+
+/// Walks through a profile's buttons one at a time, records several strikes
+/// of each, and works out a tuned `ButtonFrequency` from them. Each strike's
+/// power is read through the same window at its estimated peak, so it is on
+/// the scale the detector's windowed bank triggers on:
+/// * `frequency` the average peak of the strikes
+/// * `lower_bandwidth` and `upper_bandwidth` out to the furthest strike's
+///   peak, plus `margin_hz`, so every strike seen falls inside. The width
+///   comes from where the rod rang rather than from how wide one block's
+///   peak is, which is the window's main lobe and changes with the block
+///   length the detector is replanned for
+/// * `power_threshold` 3dB under the weakest strike
+///
+/// A strike is counted when the peak near the button's nominal frequency
+/// rises `min_snr_db` above the noise floor, and ends when it falls back
+/// under `STD_SNR_DB`.
+pub struct Calibrator {
+    profile: RemoteProfile<'static>,
+    /// How far either side of the nominal frequency to look for the peak
+    pub search_hz: f32,
+    pub min_snr_db: f32,
+    /// How far past the furthest strike the bandwidth reaches, for the error
+    /// of a peak estimated from one block and strikes a handful didn't show
+    pub margin_hz: f32,
+    button: usize,
+    strikes: [Option<Strike>; CALIBRATION_STRIKES],
+    count: usize,
+    /// The strongest block of the strike going on now, if there is one
+    current: Option<Strike>,
+    floor: NoiseFloor,
+    /// How many blocks the floor has been fed, up to `SETTLE_BLOCKS`
+    settled: u32,
+    result: CalibratedRemote,
+}
+
+impl Calibrator {
+    pub fn new(profile: RemoteProfile<'static>) -> Calibrator {
+        Calibrator {
+            result: CalibratedRemote::new(&profile),
+            profile,
+            search_hz: 750f32,
+            //a strike's power sets the button's threshold, so one worth measuring
+            //has to stand well clear of the noise
+            min_snr_db: 2f32 * STD_SNR_DB,
+            margin_hz: 50f32,
+            button: 0,
+            strikes: [None; CALIBRATION_STRIKES],
+            count: 0,
+            current: None,
+            floor: NoiseFloor::DEFAULT,
+            settled: 0,
+        }
+    }
+
+    pub fn step(&self) -> CalibrationStep {
+        if self.button == self.profile.len() {
+            CalibrationStep::Done
+        } else {
+            CalibrationStep::Listening { button: self.button, strikes: self.count }
+        }
+    }
+
+    /// The button being asked for, if there are any left
+    pub fn button(&self) -> Option<&RemoteButtonSignal<'static>> {
+        self.profile.buttons().get(self.button)
+    }
+
+    /// The profile as tuned so far; buttons not measured yet are as they were
+    pub fn result(&self) -> &CalibratedRemote {
+        &self.result
+    }
+
+    /// Feeds in one block of samples, windowed like the detector's own blocks
+    /// * `fs` The sampling frequency, as measured
+    pub fn update<const N: usize>(&mut self, sample: &[i16], window: &Window<N>, fs: f32) -> CalibrationStep {
+        let nominal = match self.button() {
+            Some(button) => button.freq.frequency,
+            None => return CalibrationStep::Done,
+        };
+        //a block with no peak inside the search still has its power read, at the
+        //nominal frequency, so the floor isn't fed nothing for it
+        let peak = estimate_peak(sample, nominal, fs, self.search_hz);
+        let strike = self.measure(sample, window, fs, peak.map_or(nominal, |p| p.frequency));
+        let power = strike.power;
+        //a strike goes on until it has rung down into the noise, so its ring
+        //can't be counted as strikes of its own
+        let min_snr_db = if self.current.is_some() { STD_SNR_DB } else { self.min_snr_db };
+
+        match peak {
+            Some(_) if self.settled == SETTLE_BLOCKS && self.floor.above(power, min_snr_db) => {
+                if self.current.is_none_or(|c| strike.power > c.power) {
+                    self.current = Some(strike);
+                }
+            }
+            _ => {
+                if let Some(strike) = self.current.take() {
+                    self.strikes[self.count] = Some(strike);
+                    self.count += 1;
+                    if self.count == CALIBRATION_STRIKES {
+                        self.tune();
+                    }
+                }
+            }
+        }
+        //the strikes are kept out of the floor, so they can't raise it
+        self.floor.update_quiet(power, STD_SNR_DB);
+        self.settled = (self.settled + 1).min(SETTLE_BLOCKS);
+        self.step()
+    }
+
+    /// Reads the power at `peak` through the window, as the detector's bank would
+    fn measure<const N: usize>(&self, sample: &[i16], window: &Window<N>, fs: f32, peak: f32) -> Strike {
+        let bank: FilterBank<1> = FilterBank::new(&[peak], fs);
+        Strike {
+            frequency: peak,
+            power: bank.process_q15_windowed(sample, window)[0],
+        }
+    }
+
+    /// Turns the strikes of the current button into its `ButtonFrequency`
+    /// and moves on to the next button
+    fn tune(&mut self) {
+        let strikes = self.strikes.iter().flatten();
+        let frequency = strikes.clone().map(|s| s.frequency).sum::<f32>() / CALIBRATION_STRIKES as f32;
+        let lower = strikes.clone().map(|s| s.frequency).fold(f32::MAX, f32::min) - self.margin_hz;
+        let upper = strikes.clone().map(|s| s.frequency).fold(f32::MIN, f32::max) + self.margin_hz;
+        let weakest = strikes.map(|s| s.power).fold(f32::MAX, f32::min);
+        self.result.buttons[self.button].freq = ButtonFrequency {
+            frequency,
+            power_threshold: weakest / 2f32,
+            upper_bandwidth: upper - frequency,
+            lower_bandwidth: frequency - lower,
+        };
+        self.button += 1;
+        self.strikes = [None; CALIBRATION_STRIKES];
+        self.count = 0;
+    }
+}
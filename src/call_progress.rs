@@ -0,0 +1,210 @@
+use libm::fabsf;
+use crate::events::{Event, EventQueue};
+use crate::goertzel::db;
+
+//the North American precise tone plan.
+//440 and 480 are only 40Hz apart, so these need a block with 20Hz bins or finer (a decimated stream)
+pub struct CallProgressFreqs;
+
+impl CallProgressFreqs {
+    pub const F350: f32 = 350f32;
+    pub const F440: f32 = 440f32;
+    pub const F480: f32 = 480f32;
+    pub const F620: f32 = 620f32;
+    //in the order CallProgressDetector::update expects their powers
+    pub const ALL: [f32; 4] = [
+        CallProgressFreqs::F350, CallProgressFreqs::F440, CallProgressFreqs::F480, CallProgressFreqs::F620,
+    ];
+}
+
+/// The tone pairs call progress tones are made from
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TonePair {
+    /// 350+440Hz
+    Dial,
+    /// 440+480Hz
+    Ringback,
+    /// 480+620Hz, for both busy and reorder
+    Busy,
+}
+
+/// What the line is saying, once the cadence has been recognised
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CallProgressTone {
+    /// 350+440Hz, steady
+    Dial,
+    /// 480+620Hz, 0.5s on / 0.5s off
+    Busy,
+    /// 480+620Hz, 0.25s on / 0.25s off
+    Reorder,
+    /// 440+480Hz, 2s on / 4s off
+    Ringback,
+}
+
+/// An on/off pattern, in milliseconds
+#[derive(Copy, Clone, Debug)]
+pub struct Cadence {
+    pub on_ms: u32,
+    pub off_ms: u32,
+}
+
+impl Cadence {
+    pub const BUSY: Cadence = Cadence { on_ms: 500, off_ms: 500 };
+    pub const REORDER: Cadence = Cadence { on_ms: 250, off_ms: 250 };
+    pub const RINGBACK: Cadence = Cadence { on_ms: 2000, off_ms: 4000 };
+    /// The fax calling tone, see `line_type`
+    pub const CNG: Cadence = Cadence { on_ms: 500, off_ms: 3000 };
+
+    /// Whether a measured on and off time are each within `tolerance`
+    /// (a fraction, 0.2 for +-20%) of this cadence
+    pub fn matches(&self, on_ms: u32, off_ms: u32, tolerance: f32) -> bool {
+        within(on_ms, self.on_ms, tolerance) && within(off_ms, self.off_ms, tolerance)
+    }
+}
+
+fn within(measured: u32, nominal: u32, tolerance: f32) -> bool {
+    let error = fabsf(measured as f32 - nominal as f32);
+    error <= nominal as f32 * tolerance
+}
+
+/// What a block must look like to hold a tone pair, and how cadences are judged
+#[derive(Copy, Clone, Debug)]
+pub struct CallProgressCriteria {
+    /// The weakest either tone of a pair may be
+    pub min_power: f32,
+    /// How far apart the two tones' levels may be, in dB
+    pub max_twist_db: f32,
+    /// How far the weaker tone of the pair must stand above the other two, in dB
+    pub min_other_margin_db: f32,
+    /// How far a measured on or off time may stray from the cadence, as a fraction
+    pub cadence_tolerance: f32,
+    /// How long dial tone must be steady before it's reported
+    pub min_dial_ms: u32,
+    /// How long the line must be quiet before the tone is forgotten;
+    /// longer than the ringback off time
+    pub silence_ms: u32,
+}
+
+impl CallProgressCriteria {
+    pub const STANDARD: CallProgressCriteria = CallProgressCriteria {
+        min_power: 1f32,
+        max_twist_db: 6f32,
+        min_other_margin_db: 10f32,
+        cadence_tolerance: 0.2,
+        min_dial_ms: 1000,
+        silence_ms: 5000,
+    };
+}
+
+/// Picks out the tone pair in each block, times how long it's on and off,
+/// and reports a `CallProgress` event when the cadence matches a new tone.
+pub struct CallProgressDetector {
+    pub criteria: CallProgressCriteria,
+    /// The pair heard in the latest blocks, if any, and since when
+    pair: Option<TonePair>,
+    since: u64,
+    /// The last completed on time, and which pair it was
+    last_on: Option<(TonePair, u32)>,
+    tone: Option<CallProgressTone>,
+    /// Microseconds since the detector started
+    now: u64,
+}
+
+impl CallProgressDetector {
+    pub fn new(criteria: CallProgressCriteria) -> CallProgressDetector {
+        CallProgressDetector {
+            criteria,
+            pair: None,
+            since: 0,
+            last_on: None,
+            tone: None,
+            now: 0,
+        }
+    }
+
+    /// The tone last recognised, until the line goes quiet
+    pub fn tone(&self) -> Option<CallProgressTone> {
+        self.tone
+    }
+
+    /// Which pair, if any, one block's powers hold
+    /// * `tones` The powers of `CallProgressFreqs::ALL`, in that order
+    pub fn detect(&self, tones: &[f32]) -> Option<TonePair> {
+        assert_eq!(tones.len(), 4);
+        let c = &self.criteria;
+        let mut order = [0usize, 1, 2, 3];
+        order.sort_unstable_by(|a, b| tones[*b].partial_cmp(&tones[*a]).unwrap_or(core::cmp::Ordering::Equal));
+        let (first, second, third) = (tones[order[0]], tones[order[1]], tones[order[2]]);
+        if second < c.min_power
+            || db(first / second) > c.max_twist_db
+            || db(second / third) < c.min_other_margin_db {
+            return None;
+        }
+        match (order[0].min(order[1]), order[0].max(order[1])) {
+            (0, 1) => Some(TonePair::Dial),
+            (1, 2) => Some(TonePair::Ringback),
+            (2, 3) => Some(TonePair::Busy),
+            _ => None,
+        }
+    }
+
+    /// Feeds in the latest block.
+    /// * `tones` The powers of `CallProgressFreqs::ALL`, in that order
+    /// * `elapsed_us` How long the block took, in microseconds
+    pub fn update(&mut self, tones: &[f32], elapsed_us: u32, events: &mut EventQueue) {
+        let block_start = self.now;
+        self.now += elapsed_us as u64;
+        let pair = self.detect(tones);
+
+        if pair != self.pair {
+            let lasted_ms = ((block_start - self.since) / 1000) as u32;
+            match (self.pair, pair) {
+                //a burst ended, keep its length for when the gap after it ends
+                (Some(was), None) => self.last_on = Some((was, lasted_ms)),
+                //a gap ended, so a whole on/off cycle has been seen
+                (None, Some(now)) => {
+                    if let Some((was, on_ms)) = self.last_on {
+                        if was == now {
+                            self.classify(now, on_ms, lasted_ms, events);
+                        }
+                    }
+                }
+                //straight from one pair to another, no cadence to go on
+                _ => self.last_on = None,
+            }
+            self.pair = pair;
+            self.since = block_start;
+        }
+
+        let held_ms = ((self.now - self.since) / 1000) as u32;
+        match self.pair {
+            Some(TonePair::Dial) if held_ms >= self.criteria.min_dial_ms => {
+                self.report(CallProgressTone::Dial, events);
+            }
+            None if held_ms >= self.criteria.silence_ms => {
+                self.tone = None;
+                self.last_on = None;
+            }
+            _ => {}
+        }
+    }
+
+    fn classify(&mut self, pair: TonePair, on_ms: u32, off_ms: u32, events: &mut EventQueue) {
+        let tolerance = self.criteria.cadence_tolerance;
+        let tone = match pair {
+            TonePair::Busy if Cadence::BUSY.matches(on_ms, off_ms, tolerance) => CallProgressTone::Busy,
+            TonePair::Busy if Cadence::REORDER.matches(on_ms, off_ms, tolerance) => CallProgressTone::Reorder,
+            TonePair::Ringback if Cadence::RINGBACK.matches(on_ms, off_ms, tolerance) => CallProgressTone::Ringback,
+            _ => return,
+        };
+        self.report(tone, events);
+    }
+
+    //only report changes, not every cycle of the same tone
+    fn report(&mut self, tone: CallProgressTone, events: &mut EventQueue) {
+        if self.tone != Some(tone) {
+            self.tone = Some(tone);
+            events.push(Event::CallProgress(tone));
+        }
+    }
+}
@@ -0,0 +1,343 @@
+use crate::events::{DialedNumber, Event, EventQueue};
+use crate::goertzel::SlidingFilter;
+
+//Bell 202 FSK, as used for on-hook Caller ID between the first and second ring
+pub struct FskFreqs;
+
+impl FskFreqs {
+    /// A 1 bit
+    pub const MARK: f32 = 1200f32;
+    /// A 0 bit
+    pub const SPACE: f32 = 2200f32;
+    pub const BAUD: f32 = 1200f32;
+}
+
+/// Single data message format: date/time and number only
+pub const SDMF: u8 = 0x04;
+/// Multiple data message format: a list of typed parameters
+pub const MDMF: u8 = 0x80;
+
+//MDMF parameter types
+const PARAM_DATE_TIME: u8 = 0x01;
+const PARAM_NUMBER: u8 = 0x02;
+const PARAM_NUMBER_ABSENT: u8 = 0x04;
+const PARAM_NAME: u8 = 0x07;
+const PARAM_NAME_ABSENT: u8 = 0x08;
+
+/// The longest name an MDMF message carries is 15 characters
+pub const MAX_CALLER_NAME_LEN: usize = 16;
+/// Type, length, up to 255 bytes of payload and the checksum
+const MAX_MESSAGE_LEN: usize = 258;
+/// How much of the 180 bit mark preamble must be heard before a message
+/// is expected; the channel seizure before it is ignored
+const MIN_MARK_BITS: f32 = 60f32;
+
+/// Why a number or name was left out
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Withheld {
+    /// 'P', blocked by the caller
+    Private,
+    /// 'O', not available
+    OutOfArea,
+}
+
+impl Withheld {
+    fn from_byte(b: u8) -> Option<Withheld> {
+        match b {
+            b'P' => Some(Withheld::Private),
+            b'O' => Some(Withheld::OutOfArea),
+            _ => None,
+        }
+    }
+}
+
+/// The month, day, hour and minute the call was sent, in local time
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CallTime {
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+}
+
+impl CallTime {
+    /// Parses the eight ASCII digits MMDDHHMM
+    fn parse(data: &[u8]) -> Option<CallTime> {
+        if data.len() != 8 {
+            return None;
+        }
+        let two = |i: usize| -> Option<u8> {
+            let (hi, lo) = (data[i], data[i + 1]);
+            if hi.is_ascii_digit() && lo.is_ascii_digit() {
+                Some((hi - b'0') * 10 + (lo - b'0'))
+            } else {
+                None
+            }
+        };
+        Some(CallTime {
+            month: two(0)?,
+            day: two(2)?,
+            hour: two(4)?,
+            minute: two(6)?,
+        })
+    }
+}
+
+/// The caller's name, up to `MAX_CALLER_NAME_LEN` ASCII characters
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CallerName {
+    chars: [u8; MAX_CALLER_NAME_LEN],
+    len: usize,
+}
+
+impl Default for CallerName {
+    fn default() -> Self {
+        CallerName::new()
+    }
+}
+
+impl CallerName {
+    pub const fn new() -> CallerName {
+        CallerName {
+            chars: [0; MAX_CALLER_NAME_LEN],
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn as_str(&self) -> &str {
+        //only ASCII is ever stored
+        core::str::from_utf8(&self.chars[..self.len]).unwrap_or("")
+    }
+
+    fn from_bytes(data: &[u8]) -> CallerName {
+        let mut name = CallerName::new();
+        for b in data.iter().filter(|b| b.is_ascii()).take(MAX_CALLER_NAME_LEN) {
+            name.chars[name.len] = *b;
+            name.len += 1;
+        }
+        name
+    }
+}
+
+/// Everything a Caller ID message said
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CallerId {
+    pub time: Option<CallTime>,
+    pub number: DialedNumber,
+    /// Set instead of `number` when the number was left out
+    pub number_withheld: Option<Withheld>,
+    /// Only MDMF messages carry a name
+    pub name: CallerName,
+    /// Set instead of `name` when the name was left out
+    pub name_withheld: Option<Withheld>,
+}
+
+impl CallerId {
+    /// Parses a whole message: type, length, payload and checksum.
+    /// Returns None if the checksum or the layout is wrong
+    pub fn parse(message: &[u8]) -> Option<CallerId> {
+        if message.len() < 3 || message.len() != message[1] as usize + 3 {
+            return None;
+        }
+        //every byte including the checksum adds up to 0
+        if message.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) != 0 {
+            return None;
+        }
+        let payload = &message[2..message.len() - 1];
+        let mut id = CallerId {
+            time: None,
+            number: DialedNumber::new(),
+            number_withheld: None,
+            name: CallerName::new(),
+            name_withheld: None,
+        };
+        match message[0] {
+            SDMF => {
+                if payload.len() < 8 {
+                    return None;
+                }
+                let (time, number) = payload.split_at(8);
+                id.time = Some(CallTime::parse(time)?);
+                id.set_number(number);
+            }
+            MDMF => {
+                let mut rest = payload;
+                while rest.len() >= 2 {
+                    let (kind, len) = (rest[0], rest[1] as usize);
+                    if rest.len() < len + 2 {
+                        return None;
+                    }
+                    let data = &rest[2..len + 2];
+                    match kind {
+                        PARAM_DATE_TIME => id.time = CallTime::parse(data),
+                        PARAM_NUMBER => id.set_number(data),
+                        PARAM_NUMBER_ABSENT => id.number_withheld = data.first().and_then(|b| Withheld::from_byte(*b)),
+                        PARAM_NAME => id.name = CallerName::from_bytes(data),
+                        PARAM_NAME_ABSENT => id.name_withheld = data.first().and_then(|b| Withheld::from_byte(*b)),
+                        //skip parameters we don't know
+                        _ => {}
+                    }
+                    rest = &rest[len + 2..];
+                }
+            }
+            _ => return None,
+        }
+        Some(id)
+    }
+
+    //SDMF sends a lone 'P' or 'O' in place of the number
+    fn set_number(&mut self, data: &[u8]) {
+        if let (1, Some(withheld)) = (data.len(), data.first().and_then(|b| Withheld::from_byte(*b))) {
+            self.number_withheld = Some(withheld);
+            return;
+        }
+        self.number.clear();
+        for b in data {
+            self.number.push(*b as char);
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Uart {
+    /// Waiting for a start bit; `mark` is how many samples of mark have been heard in a row
+    Idle { mark: usize },
+    /// Part way through a character, `t` samples after its start bit was seen
+    Receiving { t: f32, bit: usize, byte: u8 },
+}
+
+/// Demodulates Bell 202 FSK from a decimated audio stream, frames it into
+/// bytes, and reports each Caller ID message with a good checksum as a
+/// `CallerId` event.
+///
+/// `N` is the length of the mark and space sliding filters, and should be
+/// about one bit long: 8 at 9.6kHz puts the filters on 1200 and 2400Hz,
+/// close enough to tell mark from space. The stream must be continuous,
+/// since a gap of even a few samples breaks the bit timing.
+///
+/// Nothing in main feeds it yet: main samples in bursts, with the time
+/// spent processing each one unsampled, and only decimates the tail of a
+/// burst, so it has no continuous stream to give it.
+pub struct CallerIdDecoder<const N: usize> {
+    mark: SlidingFilter<N>,
+    space: SlidingFilter<N>,
+    samples_per_bit: f32,
+    /// The least mark plus space power that counts as carrier, on the
+    /// -128 to 127 scale of the ADC samples the decimator is fed
+    pub min_power: f32,
+    uart: Uart,
+    /// Whether the mark preamble has been heard, so bytes are a message
+    armed: bool,
+    message: [u8; MAX_MESSAGE_LEN],
+    len: usize,
+}
+
+impl<const N: usize> CallerIdDecoder<N> {
+    /// * `fs` The sampling frequency of the decimated stream
+    pub fn new(fs: f32) -> CallerIdDecoder<N> {
+        CallerIdDecoder {
+            mark: SlidingFilter::new(FskFreqs::MARK, fs),
+            space: SlidingFilter::new(FskFreqs::SPACE, fs),
+            samples_per_bit: fs / FskFreqs::BAUD,
+            min_power: 1f32,
+            uart: Uart::Idle { mark: 0 },
+            armed: false,
+            message: [0; MAX_MESSAGE_LEN],
+            len: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.mark.reset();
+        self.space.reset();
+        self.uart = Uart::Idle { mark: 0 };
+        self.disarm();
+    }
+
+    /// Feeds in the next stretch of the decimated stream
+    pub fn process(&mut self, sample: &[f32], events: &mut EventQueue) {
+        for x in sample {
+            self.update(*x, events);
+        }
+    }
+
+    /// Feeds in one sample of the decimated stream
+    pub fn update(&mut self, x: f32, events: &mut EventQueue) {
+        self.mark.update(x);
+        self.space.update(x);
+        let (m, s) = (self.mark.power(), self.space.power());
+        let carrier = self.mark.ready() && m + s >= self.min_power;
+        //the filters see the last bit time, so a bit reads true from half way into
+        //the next one, and an edge is seen half a bit late
+        let one = m > s;
+
+        self.uart = match self.uart {
+            Uart::Idle { .. } if !carrier => {
+                self.disarm();
+                Uart::Idle { mark: 0 }
+            }
+            Uart::Idle { mark } if one => {
+                if mark as f32 >= MIN_MARK_BITS * self.samples_per_bit && !self.armed {
+                    self.armed = true;
+                    self.len = 0;
+                }
+                Uart::Idle { mark: mark + 1 }
+            }
+            Uart::Idle { .. } => Uart::Receiving { t: 0.0, bit: 0, byte: 0 },
+            Uart::Receiving { t, bit, byte } => {
+                let t = t + 1.0;
+                if t < (bit as f32 + 0.5) * self.samples_per_bit {
+                    Uart::Receiving { t, bit, byte }
+                } else {
+                    match bit {
+                        //a start bit that didn't last was noise
+                        0 if one => Uart::Idle { mark: 0 },
+                        0 => Uart::Receiving { t, bit: 1, byte },
+                        //data, least significant bit first
+                        1..=8 => Uart::Receiving { t, bit: bit + 1, byte: byte | ((one as u8) << (bit - 1)) },
+                        _ => {
+                            if one {
+                                self.received(byte, events);
+                            } else {
+                                //framing error
+                                self.disarm();
+                            }
+                            Uart::Idle { mark: 0 }
+                        }
+                    }
+                }
+            }
+        };
+    }
+
+    fn received(&mut self, byte: u8, events: &mut EventQueue) {
+        if !self.armed {
+            return;
+        }
+        if self.len == 0 && byte != SDMF && byte != MDMF {
+            self.disarm();
+            return;
+        }
+        self.message[self.len] = byte;
+        self.len += 1;
+        if self.len >= 2 && self.len == self.message[1] as usize + 3 {
+            if let Some(id) = CallerId::parse(&self.message[..self.len]) {
+                events.push(Event::CallerId(id));
+            }
+            self.disarm();
+        }
+    }
+
+    fn disarm(&mut self) {
+        self.armed = false;
+        self.len = 0;
+    }
+}
@@ -0,0 +1,165 @@
+use crate::decimator::Decimator;
+use crate::events::{Event, EventQueue};
+use crate::goertzel::{strongest, FilterBank};
+use crate::window::{Window, WindowKind};
+
+//the standard 50 EIA CTCSS tones. Some are only 2.4Hz apart, so telling
+//them apart takes about a second of signal at a low sample rate
+pub struct CtcssTones;
+
+impl CtcssTones {
+    pub const ALL: [f32; 50] = [
+        67.0, 69.3, 71.9, 74.4, 77.0, 79.7, 82.5, 85.4, 88.5, 91.5,
+        94.8, 97.4, 100.0, 103.5, 107.2, 110.9, 114.8, 118.8, 123.0, 127.3,
+        131.8, 136.5, 141.3, 146.2, 151.4, 156.7, 159.8, 162.2, 165.5, 167.9,
+        171.3, 173.8, 177.3, 179.9, 183.5, 186.2, 189.9, 192.8, 196.6, 199.5,
+        203.5, 206.5, 210.7, 218.1, 225.7, 229.1, 233.6, 241.8, 250.3, 254.1,
+    ];
+}
+
+/// Length of the decimating filter in front of the tone bank
+const DECIMATOR_TAPS: usize = 64;
+
+/// What a block must look like to hold a CTCSS tone, and how many blocks
+/// it takes to lock on and let go
+#[derive(Copy, Clone, Debug)]
+pub struct CtcssCriteria {
+    /// The weakest a tone may be, with the input on the -128 to 127 scale
+    /// of the ADC samples main passes around
+    pub min_power: f32,
+    /// How far the strongest tone must stand above every other, in dB
+    pub min_relative_peak_db: f32,
+    /// How many blocks in a row must agree on a tone to lock on to it
+    pub lock_blocks: u32,
+    /// How many blocks in a row without the locked tone lose the lock
+    pub unlock_blocks: u32,
+}
+
+impl CtcssCriteria {
+    pub const STANDARD: CtcssCriteria = CtcssCriteria {
+        //a tone of about one count; CTCSS is sent well under the audio it rides under
+        min_power: 0.25,
+        min_relative_peak_db: 6f32,
+        lock_blocks: 2,
+        unlock_blocks: 3,
+    };
+}
+
+/// Whether a tone has been locked on to
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CtcssStatus {
+    /// No tone, or not the same one for long enough
+    Searching,
+    /// Seen a tone, but not for `lock_blocks` yet
+    Acquiring { tone: f32, blocks: u32 },
+    /// Locked on to a tone; `misses` blocks in a row have gone without it
+    Locked { tone: f32, misses: u32 },
+}
+
+/// Decimates an audio stream down to a low rate, runs the 50 tone bank over
+/// long overlapping blocks of `M` samples, and locks on to the tone found.
+/// Reports `CtcssLocked` and `CtcssLost` events as the lock comes and goes.
+///
+/// At 9.6kHz in and a factor of 8 the bank runs at 1.2kHz, where `M` of
+/// 1200 gives 1Hz bins and a new reading every half second.
+pub struct CtcssDetector<const M: usize> {
+    pub criteria: CtcssCriteria,
+    decimator: Decimator<DECIMATOR_TAPS>,
+    bank: FilterBank<50>,
+    window: Window<M>,
+    block: [f32; M],
+    filled: usize,
+    status: CtcssStatus,
+}
+
+impl<const M: usize> CtcssDetector<M> {
+    /// * `fs` The sampling frequency of the audio stream
+    /// * `factor` How far to decimate it before the tone bank
+    pub fn new(criteria: CtcssCriteria, fs: f32, factor: usize) -> CtcssDetector<M> {
+        //keep the cutoff a little above the top tone, the rest of the band is voice
+        let decimator = Decimator::new(factor, fs, 300f32);
+        CtcssDetector {
+            criteria,
+            bank: FilterBank::new(&CtcssTones::ALL, decimator.output_rate()),
+            decimator,
+            window: Window::new(WindowKind::Hann),
+            block: [0f32; M],
+            filled: 0,
+            status: CtcssStatus::Searching,
+        }
+    }
+
+    pub fn status(&self) -> CtcssStatus {
+        self.status
+    }
+
+    /// The tone locked on to, if any
+    pub fn tone(&self) -> Option<f32> {
+        match self.status {
+            CtcssStatus::Locked { tone, .. } => Some(tone),
+            _ => None,
+        }
+    }
+
+    /// Follows a change in the audio stream's sampling frequency
+    pub fn retune(&mut self, fs: f32) {
+        self.decimator.retune(fs);
+        self.bank.retune(self.decimator.output_rate());
+    }
+
+    /// Feeds in the next stretch of the audio stream
+    pub fn process(&mut self, sample: &[f32], events: &mut EventQueue) {
+        for x in sample {
+            if let Some(y) = self.decimator.update(*x) {
+                self.block[self.filled] = y;
+                self.filled += 1;
+                if self.filled == M {
+                    let tone = self.detect();
+                    self.update(tone, events);
+                    //overlap the blocks by half
+                    self.block.copy_within(M / 2.., 0);
+                    self.filled = M - M / 2;
+                }
+            }
+        }
+    }
+
+    /// The tone in the current block, if one stands out
+    fn detect(&self) -> Option<f32> {
+        let powers = self.bank.process_windowed(&self.block, &self.window);
+        let best = strongest(&powers, self.criteria.min_relative_peak_db)?;
+        if powers[best] < self.criteria.min_power {
+            return None;
+        }
+        Some(CtcssTones::ALL[best])
+    }
+
+    fn update(&mut self, heard: Option<f32>, events: &mut EventQueue) {
+        let c = &self.criteria;
+        self.status = match (self.status, heard) {
+            (CtcssStatus::Locked { tone, .. }, Some(h)) if h == tone => CtcssStatus::Locked { tone, misses: 0 },
+            (CtcssStatus::Locked { tone, misses }, _) => {
+                if misses + 1 >= c.unlock_blocks {
+                    events.push(Event::CtcssLost { tone });
+                    CtcssStatus::Searching
+                } else {
+                    CtcssStatus::Locked { tone, misses: misses + 1 }
+                }
+            }
+            (_, None) => CtcssStatus::Searching,
+            (CtcssStatus::Acquiring { tone, blocks }, Some(h)) if h == tone => {
+                if blocks + 1 >= c.lock_blocks {
+                    events.push(Event::CtcssLocked { tone });
+                    CtcssStatus::Locked { tone, misses: 0 }
+                } else {
+                    CtcssStatus::Acquiring { tone, blocks: blocks + 1 }
+                }
+            }
+            (_, Some(h)) if c.lock_blocks <= 1 => {
+                events.push(Event::CtcssLocked { tone: h });
+                CtcssStatus::Locked { tone: h, misses: 0 }
+            }
+            (_, Some(h)) => CtcssStatus::Acquiring { tone: h, blocks: 1 },
+        };
+    }
+}
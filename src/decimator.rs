@@ -0,0 +1,126 @@
+use libm::sinf;
+use crate::goertzel::PI;
+use crate::window::{Window, WindowKind};
+
+/// Low-pass filters and downsamples a stream by an integer factor, so the
+/// audio band can be picked out of the fast ADC stream for detectors
+/// that need long, continuous integration at a low rate.
+///
+/// The filter is a Hamming-windowed sinc of `TAPS` taps, and is only
+/// evaluated for the samples that are kept.
+#[derive(Debug, Copy, Clone)]
+pub struct Decimator<const TAPS: usize> {
+    /// Keep one sample in every `factor`
+    factor: usize,
+    /// The input sampling frequency
+    fs: f32,
+    coef: [f32; TAPS],
+    /// The last `TAPS` inputs, oldest at `pos`
+    history: [f32; TAPS],
+    pos: usize,
+    /// Inputs seen since the last output
+    phase: usize,
+}
+
+impl<const TAPS: usize> Decimator<TAPS> {
+    /// Returns a decimator for the given parameters
+    /// * `factor` How many input samples make one output sample
+    /// * `fs` The sampling frequency of the input
+    /// * `cutoff` The low-pass corner, below half the output rate
+    pub fn new(factor: usize, fs: f32, cutoff: f32) -> Decimator<TAPS> {
+        assert!(factor > 0 && TAPS > 0);
+        assert!(cutoff < fs / factor as f32 / 2.0);
+        //symmetric, so the taps are too and the filter is linear-phase
+        let window: Window<TAPS> = Window::symmetric(WindowKind::Hamming);
+        let fc = cutoff / fs;
+        let mid = (TAPS - 1) as f32 / 2.0;
+        let mut coef = [0f32; TAPS];
+        let mut sum = 0f32;
+        for (n, (c, w)) in coef.iter_mut().zip(window.coefficients().iter()).enumerate() {
+            let t = n as f32 - mid;
+            let sinc = if t == 0.0 { 2.0 * fc } else { sinf(2.0 * PI * fc * t) / (PI * t) };
+            *c = sinc * w;
+            sum += *c;
+        }
+        //unity gain at DC
+        for c in coef.iter_mut() {
+            *c /= sum;
+        }
+        Decimator {
+            factor,
+            fs,
+            coef,
+            history: [0f32; TAPS],
+            pos: 0,
+            phase: 0,
+        }
+    }
+
+    pub fn factor(&self) -> usize {
+        self.factor
+    }
+
+    /// The sampling frequency of the output
+    pub fn output_rate(&self) -> f32 {
+        self.fs / self.factor as f32
+    }
+
+    /// Follows a change in the input sampling frequency; the filter
+    /// itself is left as it was
+    pub fn retune(&mut self, fs: f32) {
+        self.fs = fs;
+    }
+
+    /// Empties the filter history.
+    pub fn reset(&mut self) {
+        self.history = [0f32; TAPS];
+        self.pos = 0;
+        self.phase = 0;
+    }
+
+    /// Feeds in one input sample, returning an output sample
+    /// every `factor` inputs
+    pub fn update(&mut self, x: f32) -> Option<f32> {
+        self.history[self.pos] = x;
+        self.pos = (self.pos + 1) % TAPS;
+        self.phase += 1;
+        if self.phase < self.factor {
+            return None;
+        }
+        self.phase = 0;
+        //history[pos] is now the oldest sample, which lines up with the last tap
+        let (old, new) = self.history.split_at(self.pos);
+        let y = new.iter().chain(old.iter())
+            .zip(self.coef.iter().rev())
+            .map(|(x, c)| x * c)
+            .sum();
+        Some(y)
+    }
+
+    /// Decimates `sample` into `out`, returning how many outputs were written.
+    /// `out` needs room for `sample.len() / factor + 1`
+    pub fn process(&mut self, sample: &[f32], out: &mut [f32]) -> usize {
+        self.run(sample.iter().copied(), out)
+    }
+
+    /// Decimates Q15 `sample` into `out`, returning how many outputs were
+    /// written. The output is on the same scale as the samples, like the
+    /// fixed-point filters, so the ADC's readings keep their size and the
+    /// detectors' thresholds mean the same on either side of the decimator
+    pub fn process_q15(&mut self, sample: &[i16], out: &mut [f32]) -> usize {
+        self.run(sample.iter().map(|x| *x as f32), out)
+    }
+
+    fn run(&mut self, sample: impl Iterator<Item = f32>, out: &mut [f32]) -> usize {
+        let mut written = 0;
+        for x in sample {
+            if let Some(y) = self.update(x) {
+                if written < out.len() {
+                    out[written] = y;
+                    written += 1;
+                }
+            }
+        }
+        written
+    }
+}
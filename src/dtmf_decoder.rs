@@ -0,0 +1,116 @@
+use libm::fabsf;
+use crate::dtmf_signals::{DtmfButtonSignal, DtmfFreqs, DtmfKeypad};
+use crate::goertzel::{db, strongest};
+
+//the second harmonics of the row and column tones, which speech has plenty of and DTMF doesn't.
+//the row harmonics (1394-1882Hz) sit only 58-71Hz from some of the column tones, closer than a
+//digit 1.5% off nominal can be told apart from in any block short enough to decode it
+pub struct DtmfHarmonics;
+
+impl DtmfHarmonics {
+    pub const ROW_A: f32 = 2f32 * DtmfFreqs::ROW_A;
+    pub const ROW_B: f32 = 2f32 * DtmfFreqs::ROW_B;
+    pub const ROW_C: f32 = 2f32 * DtmfFreqs::ROW_C;
+    pub const ROW_D: f32 = 2f32 * DtmfFreqs::ROW_D;
+    pub const COL_1: f32 = 2f32 * DtmfFreqs::COL_1;
+    pub const COL_2: f32 = 2f32 * DtmfFreqs::COL_2;
+    pub const COL_3: f32 = 2f32 * DtmfFreqs::COL_3;
+    pub const COL_A: f32 = 2f32 * DtmfFreqs::COL_A;
+}
+
+/// A digit that passed every check, with where it sits on the keypad
+#[derive(Clone)]
+pub struct DtmfDigit {
+    pub button: DtmfButtonSignal<'static>,
+    /// 0 for ROW_A through 3 for ROW_D
+    pub row: usize,
+    /// 0 for COL_1 through 3 for COL_A
+    pub col: usize,
+    /// Row level over column level in dB; negative when the column is louder
+    pub twist_db: f32,
+}
+
+/// The acceptance criteria, after ITU-T Q.24 and Bellcore TR-NWT-000506.
+/// Levels are in the same units as the Goertzel power readings.
+#[derive(Copy, Clone, Debug)]
+pub struct DtmfCriteria {
+    /// The weakest a row or column tone may be
+    pub min_power: f32,
+    /// How much louder the column tone may be than the row tone
+    /// ("standard" twist), in dB
+    pub max_col_over_row_db: f32,
+    /// How much louder the row tone may be than the column tone
+    /// ("reverse" twist), in dB
+    pub max_row_over_col_db: f32,
+    /// How far the strongest row must stand above the other rows, and
+    /// the strongest column above the other columns, in dB
+    pub min_relative_peak_db: f32,
+    /// How far each tone's second harmonic must sit below the tone, in dB
+    pub min_harmonic_rejection_db: f32,
+    /// How far a row's second harmonic must be from the digit's column
+    /// for the row's harmonic check to be made, in Hz. Any closer and the
+    /// column tone, off by up to Q.24's 1.5%, reads in the harmonic's filter
+    pub min_harmonic_spacing_hz: f32,
+}
+
+impl DtmfCriteria {
+    pub const STANDARD: DtmfCriteria = DtmfCriteria {
+        min_power: 1f32,
+        max_col_over_row_db: 4f32,
+        max_row_over_col_db: 8f32,
+        min_relative_peak_db: 6f32,
+        min_harmonic_rejection_db: 20f32,
+        //2.5 bins of main's ~26ms Hann-windowed block, past its main lobe, plus 1.5% either way
+        min_harmonic_spacing_hz: 145f32,
+    };
+}
+
+/// Turns the 8 DTMF tone powers for a block into at most one digit.
+pub struct DtmfDecoder {
+    pub criteria: DtmfCriteria,
+}
+
+impl DtmfDecoder {
+    pub fn new(criteria: DtmfCriteria) -> DtmfDecoder {
+        DtmfDecoder { criteria }
+    }
+
+    /// Decodes one block.
+    /// * `tones` The powers of ROW_A..ROW_D then COL_1..COL_A
+    /// * `harmonics` The powers at twice those frequencies in the same
+    ///   order, or an empty slice to skip the harmonic check
+    pub fn decode(&self, tones: &[f32], harmonics: &[f32]) -> Option<DtmfDigit> {
+        assert_eq!(tones.len(), 8);
+        let c = &self.criteria;
+        let (rows, cols) = tones.split_at(4);
+        let row = strongest(rows, c.min_relative_peak_db)?;
+        let col = strongest(cols, c.min_relative_peak_db)?;
+        let (row_pwr, col_pwr) = (rows[row], cols[col]);
+        if row_pwr < c.min_power || col_pwr < c.min_power {
+            return None;
+        }
+
+        let twist_db = db(row_pwr / col_pwr);
+        if twist_db > c.max_row_over_col_db || -twist_db > c.max_col_over_row_db {
+            return None;
+        }
+
+        let button = &DtmfKeypad::KEYS[row][col];
+        if !harmonics.is_empty() {
+            assert_eq!(harmonics.len(), 8);
+            let row_harmonic_clear = fabsf(2f32 * button.row_freq.frequency - button.col_freq.frequency)
+                >= c.min_harmonic_spacing_hz;
+            if (row_harmonic_clear && db(row_pwr / harmonics[row]) < c.min_harmonic_rejection_db)
+                || db(col_pwr / harmonics[4 + col]) < c.min_harmonic_rejection_db {
+                return None;
+            }
+        }
+
+        Some(DtmfDigit {
+            button: button.clone(),
+            row,
+            col,
+            twist_db,
+        })
+    }
+}
@@ -0,0 +1,93 @@
+use libm::{powf, sinf};
+use crate::dtmf_signals::DtmfKeypad;
+use crate::goertzel::PI;
+
+/// Synthesizes DTMF digit strings as PCM, for driving a DAC or for feeding
+/// known signals into the detectors.
+#[derive(Copy, Clone, Debug)]
+pub struct DtmfGenerator {
+    /// The sampling frequency of the output
+    pub sample_rate: f32,
+    /// The peak level of the row (low group) tone, as a fraction of full scale
+    pub amplitude: f32,
+    /// How much louder the column (high group) tone is than the row tone, in dB
+    pub twist_db: f32,
+    /// How long each digit sounds
+    pub on_ms: u32,
+    /// How long the silence after each digit lasts
+    pub off_ms: u32,
+}
+
+impl DtmfGenerator {
+    /// 70ms on / 70ms off with the column 2dB up, as a phone would send
+    pub fn new(sample_rate: f32) -> DtmfGenerator {
+        DtmfGenerator {
+            sample_rate,
+            amplitude: 0.4,
+            twist_db: 2.0,
+            on_ms: 70,
+            off_ms: 70,
+        }
+    }
+
+    fn on_samples(&self) -> usize {
+        (self.on_ms as f32 * self.sample_rate / 1000.0) as usize
+    }
+
+    fn off_samples(&self) -> usize {
+        (self.off_ms as f32 * self.sample_rate / 1000.0) as usize
+    }
+
+    /// How many samples `digits` renders to. Every character takes one
+    /// on/off slot; ones that aren't DTMF digits, such as ',', are silent
+    pub fn len(&self, digits: &str) -> usize {
+        digits.chars().count() * (self.on_samples() + self.off_samples())
+    }
+
+    /// Renders samples `start..start + out.len()` of `digits` into `out`,
+    /// in -1.0 to 1.0, so a long string can be played out in chunks.
+    /// Returns how many samples were written, less than `out.len()` once
+    /// the end of `digits` is reached
+    pub fn render_f32(&self, digits: &str, start: usize, out: &mut [f32]) -> usize {
+        self.render(digits, start, out, |x| x)
+    }
+
+    /// Renders samples `start..start + out.len()` of `digits` into `out` as
+    /// 16-bit PCM, clipping if the twist pushes the sum past full scale.
+    /// Returns how many samples were written
+    pub fn render_i16(&self, digits: &str, start: usize, out: &mut [i16]) -> usize {
+        self.render(digits, start, out, |x| (x.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
+    }
+
+    /// Walks `digits` once from the slot `start` falls in, rendering each
+    /// slot's part of `out` in turn and converting the samples with `convert`
+    fn render<T>(&self, digits: &str, start: usize, out: &mut [T], convert: impl Fn(f32) -> T) -> usize {
+        let on = self.on_samples();
+        let slot = on + self.off_samples();
+        if slot == 0 {
+            return 0;
+        }
+        let col_amplitude = self.amplitude * powf(10.0, self.twist_db / 20.0);
+        let mut written = 0;
+        for (i, c) in digits.chars().enumerate().skip(start / slot) {
+            if written == out.len() {
+                break;
+            }
+            let button = DtmfKeypad::from_char(c);
+            let from = start + written - i * slot;
+            let count = (slot - from).min(out.len() - written);
+            for (t, o) in (from..from + count).zip(out[written..written + count].iter_mut()) {
+                *o = convert(match &button {
+                    Some(button) if t < on => {
+                        let t = t as f32 / self.sample_rate;
+                        self.amplitude * sinf(2.0 * PI * button.row_freq.frequency * t)
+                            + col_amplitude * sinf(2.0 * PI * button.col_freq.frequency * t)
+                    }
+                    _ => 0.0,
+                });
+            }
+            written += count;
+        }
+        written
+    }
+}
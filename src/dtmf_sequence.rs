@@ -0,0 +1,132 @@
+use crate::events::{DialedNumber, Event, EventQueue};
+
+/// The timing rules for turning per-block digits into key presses
+#[derive(Copy, Clone, Debug)]
+pub struct DtmfTiming {
+    /// How long a digit must be heard before it counts as pressed
+    pub min_tone_ms: u32,
+    /// How long a digit must be gone before it counts as released;
+    /// shorter dropouts are bridged
+    pub min_pause_ms: u32,
+    /// How long after the last digit a dialed number is considered done
+    pub interdigit_timeout_ms: u32,
+}
+
+impl DtmfTiming {
+    pub const STANDARD: DtmfTiming = DtmfTiming {
+        min_tone_ms: 40,
+        min_pause_ms: 40,
+        interdigit_timeout_ms: 3000,
+    };
+}
+
+#[derive(Copy, Clone, Debug)]
+enum State {
+    Idle,
+    /// A digit has been heard since `since`, but not yet for long enough
+    Pending { digit: char, since: u64 },
+    /// A digit is pressed since `since`; `gap` is when it last went quiet
+    Down { digit: char, since: u64, gap: Option<u64> },
+}
+
+/// Debounces the digit decoded from each block into `DigitPressed` and
+/// `DigitReleased` events, and collects the digits into a dialed number
+/// that ends on '#' or after the inter-digit timeout.
+pub struct DtmfSequencer {
+    pub timing: DtmfTiming,
+    state: State,
+    /// Microseconds since the sequencer started
+    now: u64,
+    /// When the last digit was released, if a number is being dialed
+    last_release: Option<u64>,
+    number: DialedNumber,
+}
+
+impl DtmfSequencer {
+    pub fn new(timing: DtmfTiming) -> DtmfSequencer {
+        DtmfSequencer {
+            timing,
+            state: State::Idle,
+            now: 0,
+            last_release: None,
+            number: DialedNumber::new(),
+        }
+    }
+
+    /// The digits dialed so far in the current number
+    pub fn number(&self) -> &DialedNumber {
+        &self.number
+    }
+
+    /// Feeds in the digit decoded from the latest block, if any.
+    /// * `elapsed_us` How long since the last block ended, in microseconds
+    /// * `block_us` How long the block itself is. A block decodes a digit
+    ///   that only fills part of it, so a digit can sound for up to a block
+    ///   more than the blocks it was heard in, and is given that much
+    pub fn update(&mut self, digit: Option<char>, elapsed_us: u32, block_us: u32, events: &mut EventQueue) {
+        self.now += elapsed_us as u64;
+        let block_start = self.now.saturating_sub(block_us as u64);
+        let min_tone = (self.timing.min_tone_ms as u64 * 1000).saturating_sub(block_us as u64);
+        let min_pause = self.timing.min_pause_ms as u64 * 1000;
+        let heard = |since: u64, now: u64, events: &mut EventQueue, digit: char| {
+            if now - since >= min_tone {
+                events.push(Event::DigitPressed { digit });
+                State::Down { digit, since, gap: None }
+            } else {
+                State::Pending { digit, since }
+            }
+        };
+
+        self.state = match (self.state, digit) {
+            (State::Idle, None) => State::Idle,
+            (State::Idle, Some(d)) => heard(block_start, self.now, events, d),
+            (State::Pending { digit, since }, Some(d)) if d == digit => heard(since, self.now, events, digit),
+            (State::Pending { .. }, Some(d)) => heard(block_start, self.now, events, d),
+            //too short to be a digit
+            (State::Pending { .. }, None) => State::Idle,
+            (State::Down { digit, since, .. }, Some(d)) if d == digit => {
+                State::Down { digit, since, gap: None }
+            }
+            (State::Down { digit, since, gap }, Some(d)) => {
+                self.release(digit, since, gap.unwrap_or(block_start), events);
+                heard(block_start, self.now, events, d)
+            }
+            (State::Down { digit, since, gap }, None) => {
+                let gap = gap.unwrap_or(block_start);
+                if self.now - gap >= min_pause {
+                    self.release(digit, since, gap, events);
+                    State::Idle
+                } else {
+                    State::Down { digit, since, gap: Some(gap) }
+                }
+            }
+        };
+
+        if let (State::Idle, Some(released)) = (self.state, self.last_release) {
+            if self.now - released >= self.timing.interdigit_timeout_ms as u64 * 1000 {
+                self.finish(events);
+            }
+        }
+    }
+
+    fn release(&mut self, digit: char, since: u64, until: u64, events: &mut EventQueue) {
+        events.push(Event::DigitReleased {
+            digit,
+            duration_ms: ((until - since) / 1000) as u32,
+        });
+        if digit == '#' {
+            self.finish(events);
+        } else {
+            self.number.push(digit);
+            self.last_release = Some(until);
+        }
+    }
+
+    fn finish(&mut self, events: &mut EventQueue) {
+        if !self.number.is_empty() {
+            events.push(Event::NumberDialed(self.number));
+        }
+        self.number.clear();
+        self.last_release = None;
+    }
+}
@@ -1,4 +1,5 @@
 use spectrum_analyzer::{FrequencyLimit, FrequencySpectrum};
+use crate::noise_floor::NoiseFloor;
 
 #[derive(Clone)]
 pub struct ButtonFrequency {
@@ -17,7 +18,7 @@ pub struct DtmfButtonEval {
 }
 
 impl DtmfButtonEval {
-    pub fn from_spectrum(button: DtmfButtonSignal<'static>, spectrum: &FrequencySpectrum) -> DtmfButtonEval {
+    pub fn from_spectrum<S: PowerSpectrum>(button: DtmfButtonSignal<'static>, spectrum: &S) -> DtmfButtonEval {
         let (power_row, power_col) = button.pwr_in_spectrum(spectrum);
         DtmfButtonEval {
             button,
@@ -40,6 +41,15 @@ impl DtmfButtonEval {
         self.power_row > self.button.row_freq.power_threshold &&
             self.power_col > self.button.col_freq.power_threshold
     }
+    //like the above, but against adaptive noise floors instead of the absolute thresholds
+    pub fn either_triggered_snr(&self, row_floor: &NoiseFloor, col_floor: &NoiseFloor, min_snr_db: f32) -> bool {
+        row_floor.above(self.power_row, min_snr_db) ||
+            col_floor.above(self.power_col, min_snr_db)
+    }
+    pub fn triggered_snr(&self, row_floor: &NoiseFloor, col_floor: &NoiseFloor, min_snr_db: f32) -> bool {
+        row_floor.above(self.power_row, min_snr_db) &&
+            col_floor.above(self.power_col, min_snr_db)
+    }
 }
 
 pub struct DtmfFreqs;
@@ -66,7 +76,11 @@ pub struct DtmfButtonSignal<'a> {
 }
 
 impl DtmfButtonSignal<'static> {
-    fn pwr_in_spectrum(&self, spectrum: &FrequencySpectrum) -> (f32, f32) {
+    //the character on the key, e.g. '5' or '*'
+    pub fn to_char(&self) -> char {
+        self.short_name.chars().next().unwrap_or('?')
+    }
+    fn pwr_in_spectrum<S: PowerSpectrum>(&self, spectrum: &S) -> (f32, f32) {
         let limit_a = FrequencyLimit::Range(
             self.row_freq.frequency - self.row_freq.lower_bandwidth,
             self.row_freq.frequency + self.row_freq.upper_bandwidth,
@@ -347,15 +361,83 @@ impl DtmfSignals {
     };
 }
 
-pub fn max_pwr_in_range(spectrum: &FrequencySpectrum, btn_freq: FrequencyLimit) -> f32 {
-    let mut max = 0f32;
-    for (fr, fr_val) in spectrum.data().iter() {
-        if fr.val() > btn_freq.maybe_min().unwrap_or(0f32)
-            && fr.val() < btn_freq.maybe_max().unwrap_or(42_000f32) {
-            if fr_val.val() > max {
-                max = fr_val.val();
-            }
+//the keypad as data: const row/column tables, with keys indexed [row][col],
+//rows ROW_A..ROW_D (top to bottom) and columns COL_1..COL_A (left to right)
+pub struct DtmfKeypad;
+
+impl DtmfKeypad {
+    pub const ROWS: [f32; 4] = [DtmfFreqs::ROW_A, DtmfFreqs::ROW_B, DtmfFreqs::ROW_C, DtmfFreqs::ROW_D];
+    pub const COLS: [f32; 4] = [DtmfFreqs::COL_1, DtmfFreqs::COL_2, DtmfFreqs::COL_3, DtmfFreqs::COL_A];
+    pub const KEYS: [[DtmfButtonSignal<'static>; 4]; 4] = [
+        [DtmfSignals::_1, DtmfSignals::_2, DtmfSignals::_3, DtmfSignals::_A],
+        [DtmfSignals::_4, DtmfSignals::_5, DtmfSignals::_6, DtmfSignals::_B],
+        [DtmfSignals::_7, DtmfSignals::_8, DtmfSignals::_9, DtmfSignals::_C],
+        [DtmfSignals::_STAR, DtmfSignals::_0, DtmfSignals::_POUND, DtmfSignals::_D],
+    ];
+
+    //the 8 tone frequencies, rows then columns, in the order evaluate() expects their powers
+    pub fn frequencies() -> [f32; 8] {
+        let mut freqs = [0f32; 8];
+        freqs[..4].copy_from_slice(&Self::ROWS);
+        freqs[4..].copy_from_slice(&Self::COLS);
+        freqs
+    }
+
+    pub fn from_position(row: usize, col: usize) -> Option<DtmfButtonSignal<'static>> {
+        if row < 4 && col < 4 {
+            Some(Self::KEYS[row][col].clone())
+        } else {
+            None
+        }
+    }
+
+    pub fn from_char(key: char) -> Option<DtmfButtonSignal<'static>> {
+        let (row, col) = Self::position(key)?;
+        Self::from_position(row, col)
+    }
+
+    //the (row, col) of a key's character
+    pub fn position(key: char) -> Option<(usize, usize)> {
+        Self::iter().find(|(_, _, k)| k.to_char() == key).map(|(row, col, _)| (row, col))
+    }
+
+    //every key as (row, col, signal), row by row
+    pub fn iter() -> impl Iterator<Item=(usize, usize, DtmfButtonSignal<'static>)> {
+        (0..16).map(|i| (i / 4, i % 4, Self::KEYS[i / 4][i % 4].clone()))
+    }
+
+    //evaluates every key from the 8 tone powers, ROW_A..ROW_D then COL_1..COL_A
+    pub fn evaluate(powers: &[f32]) -> [[DtmfButtonEval; 4]; 4] {
+        assert_eq!(powers.len(), 8);
+        let (rows, cols) = powers.split_at(4);
+        core::array::from_fn(|row| core::array::from_fn(|col|
+            DtmfButtonEval::new(Self::KEYS[row][col].clone(), rows[row], cols[col])
+        ))
+    }
+}
+
+/// Anything that can be read as (frequency, value) pairs, so the button
+/// evaluations work on an FFT spectrum or a zoomed one alike
+pub trait PowerSpectrum {
+    fn for_each_bin(&self, f: &mut dyn FnMut(f32, f32));
+}
+
+impl PowerSpectrum for FrequencySpectrum {
+    fn for_each_bin(&self, f: &mut dyn FnMut(f32, f32)) {
+        for (fr, fr_val) in self.data().iter() {
+            f(fr.val(), fr_val.val());
         }
     }
+}
+
+pub fn max_pwr_in_range<S: PowerSpectrum>(spectrum: &S, btn_freq: FrequencyLimit) -> f32 {
+    let mut max = 0f32;
+    spectrum.for_each_bin(&mut |fr, fr_val| {
+        if fr > btn_freq.maybe_min().unwrap_or(0f32)
+            && fr < btn_freq.maybe_max().unwrap_or(42_000f32)
+            && fr_val > max {
+            max = fr_val;
+        }
+    });
     max
 }
\ No newline at end of file
@@ -0,0 +1,125 @@
+use libm::fabsf;
+use spectrum_analyzer::FrequencyLimit;
+use crate::dtmf_signals::PowerSpectrum;
+use crate::goertzel::{Complex, Filter};
+
+/// The estimated peak of a tone
+#[derive(Copy, Clone, Debug)]
+pub struct PeakEstimate {
+    /// The interpolated peak frequency, in Hz
+    pub frequency: f32,
+    /// The power at the peak frequency, on the same scale as `goertzel::power`
+    pub power: f32,
+}
+
+/// Jacobsen's estimator on three complex DFT values spaced one bin apart.
+/// Returns the offset of the true peak from `center`, in bins
+pub fn jacobsen(prev: Complex, center: Complex, next: Complex) -> f32 {
+    // Re[(X[k-1] - X[k+1]) / (2X[k] - X[k-1] - X[k+1])]
+    let num = Complex { re: prev.re - next.re, im: prev.im - next.im };
+    let den = Complex {
+        re: 2.0 * center.re - prev.re - next.re,
+        im: 2.0 * center.im - prev.im - next.im,
+    };
+    let den_pwr = den.power();
+    if den_pwr < f32::EPSILON {
+        return 0.0;
+    }
+    clamp_offset((num.re * den.re + num.im * den.im) / den_pwr)
+}
+
+/// Parabolic interpolation on three magnitudes spaced one bin apart,
+/// for when only the magnitude spectrum is available (e.g. an FFT).
+/// Returns the offset of the true peak from `center`, in bins
+pub fn quadratic(prev: f32, center: f32, next: f32) -> f32 {
+    let den = prev - 2.0 * center + next;
+    if fabsf(den) < f32::EPSILON {
+        return 0.0;
+    }
+    clamp_offset(0.5 * (prev - next) / den)
+}
+
+fn clamp_offset(delta: f32) -> f32 {
+    delta.clamp(-0.5, 0.5)
+}
+
+/// Estimates the peak frequency of the tone nearest `nominal` in one block
+/// of samples, searching `search_hz` either side of it. The block is
+/// evaluated with the generalized Goertzel on a grid one bin apart around
+/// `nominal`, and the strongest point is refined with `jacobsen`.
+/// Returns None if the strongest point is on the edge of the search
+pub fn estimate_peak<S: Copy + Into<f32>>(sample: &[S], nominal: f32, fs: f32, search_hz: f32) -> Option<PeakEstimate> {
+    let spacing = fs / sample.len() as f32;
+    let steps = (search_hz / spacing) as i32 + 1;
+    let at = |step: i32| dft_at(sample, nominal + step as f32 * spacing, fs);
+
+    let mut prev = at(-steps);
+    let mut center = at(-steps + 1);
+    let mut best: Option<(i32, Complex, Complex, Complex)> = None;
+    for step in -steps + 1..steps {
+        let next = at(step + 1);
+        let is_peak = center.power() >= prev.power() && center.power() > next.power();
+        let stronger = match best {
+            Some((_, _, b, _)) => center.power() > b.power(),
+            None => true,
+        };
+        if is_peak && stronger {
+            best = Some((step, prev, center, next));
+        }
+        prev = center;
+        center = next;
+    }
+
+    best.map(|(step, prev, center, next)| {
+        let frequency = nominal + (step as f32 + jacobsen(prev, center, next)) * spacing;
+        PeakEstimate {
+            frequency,
+            power: dft_at(sample, frequency, fs).power(),
+        }
+    })
+}
+
+/// The complex DFT value of one block of samples at any frequency
+fn dft_at<S: Copy + Into<f32>>(sample: &[S], f: f32, fs: f32) -> Complex {
+    if f <= 0.0 || f >= fs / 2.0 {
+        return Complex { re: 0.0, im: 0.0 };
+    }
+    let mut filter = Filter::new(f, fs);
+    for x in sample.iter() {
+        filter.update((*x).into());
+    }
+    filter.complex(sample.len())
+}
+
+/// A (frequency, value) point of a magnitude spectrum
+type Bin = (f32, f32);
+
+/// Estimates the peak frequency within `limit` of a magnitude spectrum,
+/// such as an FFT or a zoom spectrum, using `quadratic` interpolation on
+/// the strongest bin that has a neighbour on either side.
+/// Returns None if there is no such bin within `limit`
+pub fn peak_in_spectrum<S: PowerSpectrum>(spectrum: &S, limit: FrequencyLimit) -> Option<PeakEstimate> {
+    let min = limit.maybe_min().unwrap_or(0f32);
+    let max = limit.maybe_max().unwrap_or(f32::MAX);
+    //the last two bins seen, and the strongest bin so far with its neighbours
+    let mut prev: Option<Bin> = None;
+    let mut center: Option<Bin> = None;
+    let mut best: Option<(Bin, Bin, Bin)> = None;
+    spectrum.for_each_bin(&mut |fr, fr_val| {
+        if let (Some(p), Some(c)) = (prev, center) {
+            let stronger = match best {
+                Some((_, b, _)) => c.1 > b.1,
+                None => true,
+            };
+            if c.0 > min && c.0 < max && stronger {
+                best = Some((p, c, (fr, fr_val)));
+            }
+        }
+        prev = center;
+        center = Some((fr, fr_val));
+    });
+    best.map(|(prev, center, next)| PeakEstimate {
+        frequency: center.0 + quadratic(prev.1, center.1, next.1) * (next.0 - center.0),
+        power: center.1 * center.1,
+    })
+}
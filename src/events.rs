@@ -0,0 +1,146 @@
+use crate::call_progress::CallProgressTone;
+use crate::caller_id::CallerId;
+use crate::line_type::LineType;
+use crate::remote_tracker::RemoteAction;
+
+/// The most digits a dialed number can hold
+pub const MAX_DIALED_DIGITS: usize = 32;
+
+/// A number dialed as DTMF digits, up to `MAX_DIALED_DIGITS` long
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DialedNumber {
+    digits: [u8; MAX_DIALED_DIGITS],
+    len: usize,
+}
+
+impl Default for DialedNumber {
+    fn default() -> Self {
+        DialedNumber::new()
+    }
+}
+
+impl DialedNumber {
+    pub const fn new() -> DialedNumber {
+        DialedNumber {
+            digits: [0; MAX_DIALED_DIGITS],
+            len: 0,
+        }
+    }
+
+    /// Appends a digit, dropping it if the number is already full.
+    /// Returns false if it was dropped
+    pub fn push(&mut self, digit: char) -> bool {
+        if self.len == MAX_DIALED_DIGITS || !digit.is_ascii() {
+            return false;
+        }
+        self.digits[self.len] = digit as u8;
+        self.len += 1;
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn as_str(&self) -> &str {
+        //only ASCII is ever pushed
+        core::str::from_utf8(&self.digits[..self.len]).unwrap_or("")
+    }
+}
+
+/// Everything the detectors report, for sending on to whatever acts on them
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Event {
+    /// A DTMF digit has been held for the minimum tone time
+    DigitPressed { digit: char },
+    /// A DTMF digit has been let go, after `duration_ms` down
+    DigitReleased { digit: char, duration_ms: u32 },
+    /// A dialed number ended with '#' or the inter-digit timeout
+    NumberDialed(DialedNumber),
+    /// The 2600Hz SF tone went away: the trunk is off-hook
+    Seize,
+    /// The 2600Hz SF tone came on: the trunk is on-hook (idle)
+    Release,
+    /// The call progress tone on the line changed
+    CallProgress(CallProgressTone),
+    /// A Caller ID message came in with a good checksum
+    CallerId(CallerId),
+    /// Locked on to a CTCSS tone, in Hz
+    CtcssLocked { tone: f32 },
+    /// The CTCSS tone locked on to has gone
+    CtcssLost { tone: f32 },
+    /// A whole five-tone selcall address came in
+    Selcall(DialedNumber),
+    /// The line was judged to be voice, fax or modem
+    LineType(LineType),
+    /// The 2100Hz answer tone reversed phase, `interval_ms` after the
+    /// reversal before it (450ms for V.25)
+    PhaseReversal { interval_ms: Option<u32> },
+    /// A remote button, by its place in the profile and its short name, did something
+    Remote { button: usize, name: &'static str, action: RemoteAction },
+}
+
+/// How many events can wait in an `EventQueue` before the oldest are dropped
+pub const EVENT_QUEUE_LEN: usize = 16;
+
+/// A fixed-size FIFO of events between the detectors and whatever reads them
+#[derive(Copy, Clone, Debug)]
+pub struct EventQueue {
+    events: [Option<Event>; EVENT_QUEUE_LEN],
+    head: usize,
+    len: usize,
+}
+
+impl Default for EventQueue {
+    fn default() -> Self {
+        EventQueue::new()
+    }
+}
+
+impl EventQueue {
+    pub const fn new() -> EventQueue {
+        EventQueue {
+            events: [None; EVENT_QUEUE_LEN],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Queues an event, dropping the oldest one if the queue is full
+    pub fn push(&mut self, event: Event) {
+        let tail = (self.head + self.len) % EVENT_QUEUE_LEN;
+        self.events[tail] = Some(event);
+        if self.len == EVENT_QUEUE_LEN {
+            self.head = (self.head + 1) % EVENT_QUEUE_LEN;
+        } else {
+            self.len += 1;
+        }
+    }
+
+    /// Takes the oldest event off the queue
+    pub fn pop(&mut self) -> Option<Event> {
+        if self.len == 0 {
+            return None;
+        }
+        let event = self.events[self.head].take();
+        self.head = (self.head + 1) % EVENT_QUEUE_LEN;
+        self.len -= 1;
+        event
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
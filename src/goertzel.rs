@@ -1,4 +1,6 @@
-use libm::{cosf, log10f};
+use core::borrow::Borrow;
+use libm::{atan2f, cosf, log10f, sinf, sqrtf};
+use crate::window::Window;
 
 pub const PI: f32 = 3.14159265358979323846264338327950288f32;
 
@@ -30,6 +32,30 @@ pub struct Filter {
     vn: Vn
 }
 
+/// A complex DFT value, as returned by the generalized Goertzel
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Complex {
+    pub re: f32,
+    pub im: f32,
+}
+
+impl Complex {
+    /// The squared magnitude, on the same scale as `power`
+    pub fn power(&self) -> f32 {
+        self.re * self.re + self.im * self.im
+    }
+
+    /// The magnitude of the value
+    pub fn magnitude(&self) -> f32 {
+        sqrtf(self.power())
+    }
+
+    /// The phase of the value in radians, in (-PI, PI]
+    pub fn phase(&self) -> f32 {
+        atan2f(self.im, self.re)
+    }
+}
+
 impl Filter {
     /// Returns a Goertzel filter for the given parameters
     /// * `f` The frequency of the filter
@@ -55,12 +81,41 @@ impl Filter {
         kernel(sample, self.koef, &mut self.vn);
         power(self.koef, self.vn, sample.len())
     }
+
+    /// Feeds a single sample through the filter.
+    pub fn update(&mut self, x: f32) {
+        kernel([x], self.koef, &mut self.vn);
+    }
+
+    /// Returns the complex DFT value at the filter frequency, given `n`
+    /// samples have been fed through with `update` since the last reset
+    pub fn complex(&self, n: usize) -> Complex {
+        dft(self.f, self.fs, self.vn, n)
+    }
+
+    /// Process the samples using the filter, applying `window` on the way in.
+    /// Returns the resulting power of the signal at the filter frequency,
+    /// compensated for the window's gain
+    pub fn process_windowed<const N: usize>(&mut self, sample: &[f32], window: &Window<N>) -> f32 {
+        assert_eq!(sample.len(), window.len());
+        let samples = sample.iter().zip(window.coefficients().iter()).map(|(x, w)| x * w);
+        kernel(samples, self.koef, &mut self.vn);
+        window.compensate(power(self.koef, self.vn, window.len()))
+    }
+
+    /// Process the samples using the filter.
+    /// Returns the complex DFT value of the signal at the filter
+    /// frequency, which need not sit on an integer bin
+    pub fn process_complex(&mut self, sample: &[f32]) -> Complex {
+        kernel(sample, self.koef, &mut self.vn);
+        dft(self.f, self.fs, self.vn, sample.len())
+    }
 }
 
 /// The "kernel" of the Gortzel filter as an IIR filter
-pub fn kernel(sample: &[f32], k: f32, vn: &mut Vn) {
-    for x in sample.iter() {
-        let t = k * vn._1 - vn._2 + x;
+pub fn kernel<X: Borrow<f32>>(sample: impl IntoIterator<Item=X>, k: f32, vn: &mut Vn) {
+    for x in sample {
+        let t = k * vn._1 - vn._2 + x.borrow();
         vn._2 = vn._1;
         vn._1 = t;
     }
@@ -76,11 +131,422 @@ pub fn power(k: f32, vn: Vn, n: usize) -> f32 {
     rslt / (n*n) as f32
 }
 
+/// Returns the complex DFT value of the signal that has passed through
+/// a Goertzel filter at frequency `f`, using the generalized Goertzel
+/// so `f` can be any frequency rather than an integer bin.
+/// The phase is relative to the first of the `n` samples, and the
+/// value is scaled so its `power()` matches `power`.
+pub fn dft(f: f32, fs: f32, vn: Vn, n: usize) -> Complex {
+    let w = 2.0 * PI * f / fs;
+    // y = s[n-1] - e^(-jw) s[n-2], which is the DFT rotated by e^(jw(n-1))
+    let re = vn._1 - cosf(w) * vn._2;
+    let im = sinf(w) * vn._2;
+    let rot = -w * (n as f32 - 1.0);
+    let (c, s) = (cosf(rot), sinf(rot));
+    Complex {
+        re: (re * c - im * s) / n as f32,
+        im: (re * s + im * c) / n as f32,
+    }
+}
+
+/// Returns a power ratio in dB, with a ratio of 0 read as the smallest
+/// positive f32 rather than minus infinity
+pub fn db(ratio: f32) -> f32 {
+    10f32 * log10f(ratio.max(f32::EPSILON))
+}
+
+/// The index of the strongest of `powers`, if it stands at least
+/// `margin_db` above all the others
+pub fn strongest(powers: &[f32], margin_db: f32) -> Option<usize> {
+    let mut best = 0;
+    for (i, p) in powers.iter().enumerate() {
+        if *p > powers[best] {
+            best = i;
+        }
+    }
+    for (i, p) in powers.iter().enumerate() {
+        if i != best && db(powers[best] / *p) < margin_db {
+            return None;
+        }
+    }
+    Some(best)
+}
+
 /// Returns the dBm of the given power of a signal
 pub fn dbm(power: f32) -> f32 {
     10.0 * log10f(2.0 * power * 1000.0 / 600.0)
 }
 
+/// A Goertzel filter over a sliding window of the last `N` samples,
+/// for continuous monitoring without gaps between blocks.
+///
+/// Each new sample is fed through a comb (`x[n] - x[n-N]`) into the
+/// same IIR `kernel` as the block filter, so the power can be read
+/// after any sample. The comb only cancels exactly on an integer bin,
+/// so the filter frequency is snapped to the nearest bin of `N`.
+#[derive(Debug, Copy, Clone)]
+pub struct SlidingFilter<const N: usize> {
+    /// The frequency of the filter, snapped to the nearest bin
+    f: f32,
+    /// The sampling frequency
+    fs: f32,
+    /// The Goertzel coefficient for the snapped frequency
+    koef: f32,
+    /// The running values of the Goertzel filter calculation.
+    vn: Vn,
+    /// The last `N` samples, oldest at `pos`
+    window: [f32; N],
+    /// Where the next sample goes in `window`
+    pos: usize,
+    /// How many samples have been seen, up to `N`
+    filled: usize,
+}
+
+impl<const N: usize> SlidingFilter<N> {
+    /// Returns a sliding Goertzel filter for the given parameters
+    /// * `f` The frequency of the filter, snapped to the nearest bin
+    /// * `fs`  The sampling frequency of the samples to process
+    pub fn new(f: f32, fs: f32) -> SlidingFilter<N> {
+        assert!(N > 0);
+        let bin = libm::roundf(f * N as f32 / fs);
+        let f = bin * fs / N as f32;
+        SlidingFilter {
+            f,
+            fs,
+            koef: calc_koef(f, fs),
+            vn: Vn { _1: 0.0, _2: 0.0 },
+            window: [0.0; N],
+            pos: 0,
+            filled: 0,
+        }
+    }
+
+    /// The frequency the filter is actually tuned to
+    pub fn frequency(&self) -> f32 {
+        self.f
+    }
+
+    /// The sampling frequency the filter is tuned for
+    pub fn sample_rate(&self) -> f32 {
+        self.fs
+    }
+
+    /// Resets the filter and empties the window.
+    pub fn reset(&mut self) {
+        self.vn = Vn { _1: 0.0, _2: 0.0 };
+        self.window = [0.0; N];
+        self.pos = 0;
+        self.filled = 0;
+    }
+
+    /// True once a full window of `N` samples has been seen
+    pub fn ready(&self) -> bool {
+        self.filled == N
+    }
+
+    /// Slides the window forward by one sample.
+    pub fn update(&mut self, x: f32) {
+        let oldest = self.window[self.pos];
+        self.window[self.pos] = x;
+        self.pos += 1;
+        if self.filled < N {
+            self.filled += 1;
+        }
+        if self.pos == N {
+            // The window is in order again, so re-run the block kernel
+            // over it to drop any rounding the recursion has picked up.
+            self.pos = 0;
+            self.vn = Vn { _1: 0.0, _2: 0.0 };
+            kernel(self.window.iter(), self.koef, &mut self.vn);
+        } else {
+            kernel([x - oldest], self.koef, &mut self.vn);
+        }
+    }
+
+    /// Slides the window over all of `sample`.
+    /// Returns the power of the signal at the filter frequency
+    /// over the last `N` samples
+    pub fn process(&mut self, sample: &[f32]) -> f32 {
+        for x in sample.iter() {
+            self.update(*x);
+        }
+        self.power()
+    }
+
+    /// Returns the power of the signal at the filter frequency
+    /// over the last `N` samples
+    pub fn power(&self) -> f32 {
+        power(self.koef, self.vn, N)
+    }
+}
+
+/// Q31 scale, 1.0 in Q31 fixed point
+const Q31: f32 = 2147483648.0;
+/// Q15 scale, 1.0 in Q15 fixed point
+const Q15: f32 = 32768.0;
+
+/// cos(2 PI f / fs) in Q31, which is half the Goertzel coefficient
+fn calc_koef_q31(f: f32, fs: f32) -> i32 {
+    let cos = calc_koef(f, fs) / 2.0;
+    // cos can round to exactly 1.0, which Q31 can't hold
+    libm::fminf(cos * Q31, i32::MAX as f32) as i32
+}
+
+/// Multiplies `v` by the Q31 value `c`, splitting `v` so the
+/// partial products can't overflow an `i64`
+fn mul_q31(v: i64, c: i32) -> i64 {
+    let hi = (v >> 31).saturating_mul(c as i64);
+    let lo = ((v & 0x7FFF_FFFF) * c as i64) >> 31;
+    hi.saturating_add(lo)
+}
+
+/// Helper for remembering the last two filter "running values" for
+/// the fixed-point Goertzel. They are kept in 64 bits so a block of
+/// Q15 samples has headroom for the filter gain, and saturate rather
+/// than wrap if that headroom is ever exceeded.
+#[derive(Copy, Clone, Debug)]
+pub struct VnFixed {
+    _1: i64,
+    _2: i64
+}
+
+/// Holds data for a fixed-point Goertzel filter that works directly
+/// on Q15 (`i16`) PCM, for parts without an FPU
+#[derive(Debug, Copy, Clone)]
+pub struct FixedFilter {
+    /// The frequency of the filter
+    f: f32,
+    /// The sampling frequency
+    fs: f32,
+    /// Half the Goertzel coefficient, cos(2 PI f / fs), in Q31
+    koef: i32,
+    /// The running values of the Goertzel filter calculation.
+    vn: VnFixed
+}
+
+impl FixedFilter {
+    /// Returns a fixed-point Goertzel filter for the given parameters
+    /// * `f` The frequency of the filter
+    /// * `fs`  The sampling frequency of the samples to process
+    pub fn new(f: f32, fs: f32) -> FixedFilter {
+        FixedFilter {
+            f,
+            fs,
+            koef: calc_koef_q31(f, fs),
+            vn: VnFixed { _1: 0, _2: 0 }
+        }
+    }
+
+    /// The frequency of the filter
+    pub fn frequency(&self) -> f32 {
+        self.f
+    }
+
+    /// The sampling frequency the filter is tuned for
+    pub fn sample_rate(&self) -> f32 {
+        self.fs
+    }
+
+    /// Resets the filter so that we can start it over again.
+    pub fn reset(&mut self) {
+        self.vn._1 = 0;
+        self.vn._2 = 0;
+    }
+
+    /// Process the samples using the filter.
+    /// Returns the resulting power of the signal at the filter frequency,
+    /// on the same scale as `Filter::process` given the same samples as f32
+    pub fn process(&mut self, sample: &[i16]) -> f32 {
+        kernel_q15(sample, self.koef, &mut self.vn);
+        power_q15(self.koef, self.vn, sample.len())
+    }
+}
+
+/// The "kernel" of the Goertzel filter in fixed point, with `k` being
+/// cos(2 PI f / fs) in Q31
+pub fn kernel_q15(sample: &[i16], k: i32, vn: &mut VnFixed) {
+    for x in sample.iter() {
+        let t = mul_q31(vn._1, k)
+            .saturating_mul(2)
+            .saturating_sub(vn._2)
+            .saturating_add(*x as i64);
+        vn._2 = vn._1;
+        vn._1 = t;
+    }
+}
+
+/// Returns the power of the signal that has passed through a
+/// fixed-point Goertzel filter. Only this last step, once per block,
+/// is done in floating point.
+pub fn power_q15(k: i32, vn: VnFixed, n: usize) -> f32 {
+    power(
+        2.0 * k as f32 / Q31,
+        Vn { _1: vn._1 as f32, _2: vn._2 as f32 },
+        n,
+    )
+}
+
+/// A bank of up to `N` Goertzel filters that are all updated in a
+/// single pass over the samples, with results returned by index in the
+/// order the frequencies were given.
+#[derive(Debug, Copy, Clone)]
+pub struct FilterBank<const N: usize> {
+    /// The frequencies of the filters
+    f: [f32; N],
+    /// The sampling frequency
+    fs: f32,
+    /// The Goertzel coefficients for the f32 path
+    koef: [f32; N],
+    /// Half the Goertzel coefficients in Q31 for the fixed-point path
+    koef_q31: [i32; N],
+    /// How many of the `N` slots are in use
+    len: usize,
+}
+
+impl<const N: usize> FilterBank<N> {
+    /// Returns a Goertzel filter bank for the given parameters
+    /// * `freqs` The frequencies of the filters, at most `N` of them
+    /// * `fs`  The sampling frequency of the samples to process
+    pub fn new(freqs: &[f32], fs: f32) -> FilterBank<N> {
+        assert!(freqs.len() <= N);
+        let mut bank = FilterBank {
+            f: [0.0; N],
+            fs,
+            koef: [0.0; N],
+            koef_q31: [0; N],
+            len: freqs.len(),
+        };
+        for (i, f) in freqs.iter().enumerate() {
+            bank.f[i] = *f;
+            bank.koef[i] = calc_koef(*f, fs);
+            bank.koef_q31[i] = calc_koef_q31(*f, fs);
+        }
+        bank
+    }
+
+    /// How many filters are in the bank
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// True if the bank has no filters
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The frequency of the filter at index `i`
+    pub fn frequency(&self, i: usize) -> f32 {
+        self.f[i]
+    }
+
+    /// The sampling frequency the coefficients are tuned for
+    pub fn sample_rate(&self) -> f32 {
+        self.fs
+    }
+
+    /// Recalculates every coefficient for a new sampling frequency
+    pub fn retune(&mut self, fs: f32) {
+        self.fs = fs;
+        for i in 0..self.len {
+            self.koef[i] = calc_koef(self.f[i], fs);
+            self.koef_q31[i] = calc_koef_q31(self.f[i], fs);
+        }
+    }
+
+    /// Retunes to the measured sampling frequency `fs` if it has moved
+    /// more than `tolerance` (as a fraction, e.g. 0.002) from the rate the
+    /// coefficients are tuned for. Returns true if it retuned
+    pub fn track(&mut self, fs: f32, tolerance: f32) -> bool {
+        if libm::fabsf(fs - self.fs) > tolerance * self.fs {
+            self.retune(fs);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Process one block of samples through every filter.
+    /// Returns the power at each filter frequency by index; slots past
+    /// `len()` are left at 0
+    pub fn process(&self, sample: &[f32]) -> [f32; N] {
+        self.run(sample.iter().cloned(), sample.len())
+    }
+
+    /// Process one block of samples through every filter, applying
+    /// `window` on the way in. Returns the power at each filter frequency
+    /// by index, compensated for the window's gain
+    pub fn process_windowed<const M: usize>(&self, sample: &[f32], window: &Window<M>) -> [f32; N] {
+        assert_eq!(sample.len(), window.len());
+        let samples = sample.iter().zip(window.coefficients().iter()).map(|(x, w)| x * w);
+        let mut result = self.run(samples, window.len());
+        for p in result[..self.len].iter_mut() {
+            *p = window.compensate(*p);
+        }
+        result
+    }
+
+    /// Process one block of Q15 samples through every filter in fixed
+    /// point. Returns the power at each filter frequency by index, on
+    /// the same scale as `process`
+    pub fn process_q15(&self, sample: &[i16]) -> [f32; N] {
+        self.run_q15(sample.iter().map(|x| (*x as i64) << 15), sample.len())
+    }
+
+    /// Process one block of Q15 samples through every filter in fixed
+    /// point, applying `window` on the way in. Returns the power at each
+    /// filter frequency by index, compensated for the window's gain
+    pub fn process_q15_windowed<const M: usize>(&self, sample: &[i16], window: &Window<M>) -> [f32; N] {
+        assert_eq!(sample.len(), window.len());
+        //the products keep the window's 15 fractional bits, so a block of
+        //small samples isn't rounded down to a few levels on the way in
+        let samples = sample.iter().zip(window.coefficients_q15().iter())
+            .map(|(x, w)| *x as i64 * *w as i64);
+        let mut result = self.run_q15(samples, window.len());
+        for p in result[..self.len].iter_mut() {
+            *p = window.compensate(*p);
+        }
+        result
+    }
+
+    /// The single pass over the samples for the f32 path
+    fn run(&self, sample: impl Iterator<Item=f32>, n: usize) -> [f32; N] {
+        let mut vn = [Vn { _1: 0.0, _2: 0.0 }; N];
+        for x in sample {
+            for (v, k) in vn[..self.len].iter_mut().zip(self.koef.iter()) {
+                let t = k * v._1 - v._2 + x;
+                v._2 = v._1;
+                v._1 = t;
+            }
+        }
+        let mut result = [0f32; N];
+        for (p, (v, k)) in result[..self.len].iter_mut().zip(vn.iter().zip(self.koef.iter())) {
+            *p = power(*k, *v, n);
+        }
+        result
+    }
+
+    /// The single pass over the samples for the fixed-point path, with each
+    /// sample carrying 15 fractional bits
+    fn run_q15(&self, sample: impl Iterator<Item=i64>, n: usize) -> [f32; N] {
+        let mut vn = [VnFixed { _1: 0, _2: 0 }; N];
+        for x in sample {
+            for (v, k) in vn[..self.len].iter_mut().zip(self.koef_q31.iter()) {
+                let t = mul_q31(v._1, *k)
+                    .saturating_mul(2)
+                    .saturating_sub(v._2)
+                    .saturating_add(x);
+                v._2 = v._1;
+                v._1 = t;
+            }
+        }
+        let mut result = [0f32; N];
+        for (p, (v, k)) in result[..self.len].iter_mut().zip(vn.iter().zip(self.koef_q31.iter())) {
+            *p = power_q15(*k, *v, n) / (Q15 * Q15);
+        }
+        result
+    }
+}
+
 /*
 This code was modified from https://github.com/sveljko/goertzel
 
@@ -0,0 +1,304 @@
+use libm::fabsf;
+use crate::call_progress::Cadence;
+use crate::events::{Event, EventQueue};
+use crate::goertzel::{Filter, PI};
+
+//the tones a fax or modem sends when it calls or answers
+pub struct LineTypeFreqs;
+
+impl LineTypeFreqs {
+    /// CNG, sent by a calling fax
+    pub const CNG: f32 = 1100f32;
+    /// CED/ANS, sent by an answering fax or modem
+    pub const ANS: f32 = 2100f32;
+}
+
+/// What's on the other end of the line
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LineType {
+    /// Energy on the line, but none of the fax or modem tones
+    Voice,
+    /// CNG, or a plain CED answer tone
+    Fax,
+    /// An answer tone with phase reversals (V.25 ANS) or 15Hz amplitude
+    /// modulation (V.8 ANSam)
+    Modem,
+}
+
+/// How long each block is, short enough to see the answer tone's phase
+/// reversals and 15Hz modulation
+const BLOCK_MS: f32 = 10f32;
+/// How long the answer tone may drop out and still count as the same
+/// tone; the block a reversal falls in can cancel itself out
+const MAX_DROPOUT_MS: u32 = 40;
+/// How many block magnitudes the modulation depth is judged over, a
+/// little more than one 15Hz cycle
+const AM_BLOCKS: usize = 8;
+
+/// What a block must look like to hold a tone, and how the line is judged
+#[derive(Copy, Clone, Debug)]
+pub struct LineTypeCriteria {
+    /// The weakest a tone may be, with the input on the -128 to 127 scale
+    /// of the ADC samples main passes around
+    pub min_power: f32,
+    /// How much of the block's energy must be in the tone, 0.0 to 1.0;
+    /// speech spreads its energy, a fax or modem tone doesn't
+    pub min_purity: f32,
+    /// How far a CNG on or off time may stray from the cadence, as a fraction
+    pub cadence_tolerance: f32,
+    /// How long the answer tone must be heard before it's judged
+    pub min_answer_ms: u32,
+    /// How deep the answer tone's modulation must be to count as ANSam,
+    /// as (max - min) / (max + min); ANSam is 0.2
+    pub min_am_depth: f32,
+    /// The quietest block, by mean square on the same scale, that counts as active
+    pub min_voice_energy: f32,
+    /// How long a window voice is judged over; any fax or modem tone starts it again
+    pub voice_ms: u32,
+    /// How much of a window must be active blocks to call it voice, 0.0 to 1.0;
+    /// speech pauses between words and for breath, so it's never all of it
+    pub min_voice_share: f32,
+}
+
+impl LineTypeCriteria {
+    pub const STANDARD: LineTypeCriteria = LineTypeCriteria {
+        min_power: 1f32,
+        min_purity: 0.5,
+        cadence_tolerance: 0.2,
+        min_answer_ms: 1000,
+        min_am_depth: 0.1,
+        //about 1.4 counts RMS, clear of a quiet line's hiss on the 8 bit ADC
+        min_voice_energy: 2f32,
+        voice_ms: 1500,
+        min_voice_share: 0.25,
+    };
+}
+
+/// Classifies the line as fax, modem or voice from a continuous audio
+/// stream, reporting `LineType` events as it changes its mind and a
+/// `PhaseReversal` event for each reversal in the answer tone.
+///
+/// The answer tone's phase is followed across blocks with the complex
+/// Goertzel, so the stream must not have gaps in it. Once the line is
+/// called fax or modem it stays that way, since the data that follows
+/// would look like voice, until `reset` at the end of the call.
+pub struct LineTypeDetector {
+    pub criteria: LineTypeCriteria,
+    fs: f32,
+    block_len: usize,
+    cng: Filter,
+    ans: Filter,
+    /// Samples and summed energy in the current block
+    n: usize,
+    energy: f32,
+    /// Samples since the detector started
+    now: u64,
+    /// Since when CNG has been on or off, and how long it was last on
+    cng_on: bool,
+    cng_since: u64,
+    cng_burst_ms: Option<u32>,
+    /// Since when the answer tone has been on, if it is, and when it was last heard
+    ans_since: Option<u64>,
+    ans_heard: u64,
+    /// The answer tone's phase in the last block it was heard in and that
+    /// block's start, and how far it drifts each block beyond the nominal frequency
+    last_phase: Option<(f32, u64)>,
+    drift: f32,
+    reversals: u32,
+    last_reversal: Option<u64>,
+    /// The answer tone's magnitude over the last few blocks
+    am: [f32; AM_BLOCKS],
+    am_filled: usize,
+    /// How many blocks judged the modulation deep enough, out of how many
+    am_deep: u32,
+    am_judged: u32,
+    /// How many blocks of the current voice window there have been, and how many were active
+    voice_blocks: u32,
+    voice_active: u32,
+    line_type: Option<LineType>,
+}
+
+impl LineTypeDetector {
+    /// * `fs` The sampling frequency of the audio stream
+    pub fn new(criteria: LineTypeCriteria, fs: f32) -> LineTypeDetector {
+        LineTypeDetector {
+            criteria,
+            fs,
+            block_len: (fs * BLOCK_MS / 1000f32) as usize,
+            cng: Filter::new(LineTypeFreqs::CNG, fs),
+            ans: Filter::new(LineTypeFreqs::ANS, fs),
+            n: 0,
+            energy: 0f32,
+            now: 0,
+            cng_on: false,
+            cng_since: 0,
+            cng_burst_ms: None,
+            ans_since: None,
+            ans_heard: 0,
+            last_phase: None,
+            drift: 0f32,
+            reversals: 0,
+            last_reversal: None,
+            am: [0f32; AM_BLOCKS],
+            am_filled: 0,
+            am_deep: 0,
+            am_judged: 0,
+            voice_blocks: 0,
+            voice_active: 0,
+            line_type: None,
+        }
+    }
+
+    /// What the line has been judged to be, if anything yet
+    pub fn line_type(&self) -> Option<LineType> {
+        self.line_type
+    }
+
+    /// Forgets everything heard, for the start of the next call
+    pub fn reset(&mut self) {
+        *self = LineTypeDetector::new(self.criteria, self.fs);
+    }
+
+    /// Feeds in the next stretch of the audio stream
+    pub fn process(&mut self, sample: &[f32], events: &mut EventQueue) {
+        for x in sample {
+            self.cng.update(*x);
+            self.ans.update(*x);
+            self.energy += x * x;
+            self.n += 1;
+            if self.n == self.block_len {
+                self.block(events);
+                self.cng.reset();
+                self.ans.reset();
+                self.energy = 0f32;
+                self.n = 0;
+            }
+        }
+    }
+
+    fn ms(&self, samples: u64) -> u32 {
+        (samples as f32 * 1000f32 / self.fs) as u32
+    }
+
+    fn block(&mut self, events: &mut EventQueue) {
+        let block_start = self.now;
+        self.now += self.n as u64;
+        let mean_square = self.energy / self.n as f32;
+        let cng = self.cng.complex(self.n);
+        let ans = self.ans.complex(self.n);
+        //a sine's power is a quarter of its amplitude squared, its mean square half
+        let tone = |power: f32| power >= self.criteria.min_power && 2f32 * power >= self.criteria.min_purity * mean_square;
+        let (cng_on, ans_on) = (tone(cng.power()), tone(ans.power()));
+
+        self.update_cng(cng_on, block_start, events);
+
+        if ans_on {
+            let since = *self.ans_since.get_or_insert(block_start);
+            self.ans_heard = self.now;
+            self.follow_phase(ans.phase(), block_start, events);
+            self.follow_am(ans.magnitude());
+            if self.ms(self.now - since) >= self.criteria.min_answer_ms {
+                let am = self.am_judged > 0 && self.am_deep * 2 >= self.am_judged;
+                let kind = if self.reversals > 0 || am { LineType::Modem } else { LineType::Fax };
+                self.report(kind, events);
+            }
+        } else if self.ms(self.now - self.ans_heard) > MAX_DROPOUT_MS {
+            self.ans_since = None;
+            self.last_phase = None;
+            self.drift = 0f32;
+            self.reversals = 0;
+            self.last_reversal = None;
+            self.am_filled = 0;
+            self.am_deep = 0;
+            self.am_judged = 0;
+        }
+
+        if cng_on || ans_on {
+            self.voice_blocks = 0;
+            self.voice_active = 0;
+        } else {
+            self.voice_blocks += 1;
+            if mean_square >= self.criteria.min_voice_energy {
+                self.voice_active += 1;
+            }
+            if self.ms(self.voice_blocks as u64 * self.block_len as u64) >= self.criteria.voice_ms {
+                let share = self.voice_active as f32 / self.voice_blocks as f32;
+                if self.line_type.is_none() && share >= self.criteria.min_voice_share {
+                    self.report(LineType::Voice, events);
+                }
+                self.voice_blocks = 0;
+                self.voice_active = 0;
+            }
+        }
+    }
+
+    fn update_cng(&mut self, on: bool, block_start: u64, events: &mut EventQueue) {
+        if on == self.cng_on {
+            return;
+        }
+        let lasted_ms = self.ms(block_start - self.cng_since);
+        if on {
+            //a gap ended, so a whole on/off cycle has been seen
+            if let Some(on_ms) = self.cng_burst_ms {
+                if Cadence::CNG.matches(on_ms, lasted_ms, self.criteria.cadence_tolerance) {
+                    self.report(LineType::Fax, events);
+                }
+            }
+        } else {
+            self.cng_burst_ms = Some(lasted_ms);
+        }
+        self.cng_on = on;
+        self.cng_since = block_start;
+    }
+
+    /// Compares the phase with where a steady 2100Hz tone would have got
+    /// to over one block; a jump of about half a turn is a reversal
+    fn follow_phase(&mut self, phase: f32, block_start: u64, events: &mut EventQueue) {
+        if let Some((last, at)) = self.last_phase {
+            let samples = (block_start - at) as f32;
+            let advance = 2f32 * PI * LineTypeFreqs::ANS * samples / self.fs;
+            let blocks = samples / self.n as f32;
+            let deviation = wrap(phase - last - advance);
+            let jump = wrap(deviation - self.drift * blocks);
+            if fabsf(jump) > PI / 2f32 {
+                self.reversals += 1;
+                let interval_ms = self.last_reversal.map(|at| self.ms(block_start - at));
+                self.last_reversal = Some(block_start);
+                events.push(Event::PhaseReversal { interval_ms });
+            } else {
+                //the tone is allowed to sit a little off 2100Hz, which shows up as a steady drift
+                self.drift += 0.3 * jump / blocks;
+            }
+        }
+        self.last_phase = Some((phase, block_start));
+    }
+
+    fn follow_am(&mut self, magnitude: f32) {
+        self.am.copy_within(1.., 0);
+        self.am[AM_BLOCKS - 1] = magnitude;
+        self.am_filled = (self.am_filled + 1).min(AM_BLOCKS);
+        if self.am_filled < AM_BLOCKS {
+            return;
+        }
+        let max = self.am.iter().cloned().fold(f32::MIN, f32::max);
+        let min = self.am.iter().cloned().fold(f32::MAX, f32::min);
+        self.am_judged += 1;
+        if (max - min) / (max + min).max(f32::EPSILON) >= self.criteria.min_am_depth {
+            self.am_deep += 1;
+        }
+    }
+
+    //only report changes; voice never overrides fax or modem
+    fn report(&mut self, kind: LineType, events: &mut EventQueue) {
+        if self.line_type != Some(kind) {
+            self.line_type = Some(kind);
+            events.push(Event::LineType(kind));
+        }
+    }
+}
+
+/// Wraps an angle into (-PI, PI]
+fn wrap(angle: f32) -> f32 {
+    let turns = libm::roundf(angle / (2f32 * PI));
+    angle - turns * 2f32 * PI
+}
@@ -5,6 +5,27 @@
 pub mod dtmf_signals;
 pub mod space_command_remote;
 pub mod goertzel;
+pub mod window;
+pub mod planner;
+pub mod sample_rate;
+pub mod estimator;
+pub mod zoom;
+pub mod noise_floor;
+pub mod dtmf_decoder;
+pub mod dtmf_sequence;
+pub mod events;
+pub mod dtmf_generator;
+pub mod mf_signals;
+pub mod sf_signals;
+pub mod call_progress;
+pub mod decimator;
+pub mod caller_id;
+pub mod ctcss;
+pub mod selcall;
+pub mod line_type;
+pub mod calibration;
+pub mod remote_tracker;
+pub mod strike_envelope;
 
 use core::alloc::Layout;
 // use panic_semihosting as _;
@@ -15,6 +36,7 @@ use daisy_bsp as daisy;
 use daisy::led::Led;
 
 use cortex_m::asm;
+use cortex_m::peripheral::DWT;
 use alloc_cortex_m::CortexMHeap;
 
 use libm::sqrtf;
@@ -44,7 +66,6 @@ use adafruit_led_backpack::*;
 use daisy_bsp::hal::gpio::gpiob::PB6;
 // use daisy::pac::rtc;
 // use daisy::pac::RTC;
-use hashbrown::HashMap;
 use embedded_time::Clock;
 use fugit::Instant;
 use ht16k33::{Display, HT16K33, LedLocation};
@@ -57,7 +78,19 @@ use spectrum_analyzer::{samples_fft_to_spectrum, FrequencyValue, FrequencyLimit,
 use crate::hal::gpio::Output;
 
 use crate::{dtmf_signals::*, space_command_remote::*};
-use crate::goertzel::Filter;
+use crate::goertzel::FilterBank;
+use crate::window::{Window, WindowKind};
+use crate::planner::{plan, PlanConstraints};
+use crate::sample_rate::SampleRateMeter;
+use crate::noise_floor::{NoiseFloor, STD_SNR_DB};
+use crate::dtmf_decoder::{DtmfCriteria, DtmfDecoder, DtmfHarmonics};
+use crate::dtmf_sequence::{DtmfSequencer, DtmfTiming};
+use crate::events::{Event, EventQueue};
+use crate::sf_signals::{SfCriteria, SfDetector, SfFreqs, SfTiming};
+use crate::calibration::{CalibratedRemote, CalibrationStep, Calibrator};
+use crate::remote_tracker::{RemoteAction, RemoteButtonTracker, RemoteTiming};
+use crate::strike_envelope::{StrikeCriteria, StrikeValidator};
+use crate::decimator::Decimator;
 
 #[global_allocator]
 static ALLOCATOR: CortexMHeap = CortexMHeap::empty();
@@ -76,7 +109,7 @@ fn main() -> ! {
     // - board setup ----------------------------------------------------------
 
     let board = daisy::Board::take().unwrap();
-    let cp = cortex_m::Peripherals::take().unwrap();
+    let mut cp = cortex_m::Peripherals::take().unwrap();
     let dp = daisy::pac::Peripherals::take().unwrap();
     // Constrain and Freeze power
     let pwr = dp.PWR.constrain();
@@ -102,6 +135,10 @@ fn main() -> ! {
                                  dp.GPIOF.split(ccdr.peripheral.GPIOF),
                                  dp.GPIOG.split(ccdr.peripheral.GPIOG));
 
+    // the cycle counter times each block so we know the real sample rate
+    cp.DCB.enable_trace();
+    cp.DWT.enable_cycle_counter();
+
     let mut delay = Delay::new(cp.SYST, ccdr.clocks);
 
     // let mut rtc = rtc:Rtc::open_or_init(
@@ -130,24 +167,96 @@ fn main() -> ! {
     adc1.set_resolution(adc::Resolution::EIGHTBIT);
     adc1.set_sample_time(T_1);
 
+    //the longest remote block; the block length itself is planned for the remote's buttons
     const BUFFER_SIZE: usize = 2048;
+    //the longest the loop can capture in one go, DTMF_BLOCKS DTMF blocks' worth of remote blocks
+    const CAPTURE_SIZE: usize = 14 * BUFFER_SIZE;
     const SAMPLE_RATE: u32 = 430_000;
     const SCALE_FACTOR: i16 = 256i16 / 2;
     //ccdr.clocks.sys_ck().0 as f32 / 65_535.;
     //loggit!("Scale Factor:{:?}", SCALE_FACTOR);
 
-    //set up goertzel filters for each of the 12 frequencies we are interested in
-    let freqs = [
-        RemFreqs::CHANNEL_DN, RemFreqs::VOLUME, RemFreqs::OFF_ON, RemFreqs::CHANNEL_UP,
+    //the DTMF rows sit only ~70Hz apart, which takes a block of ~25ms to tell apart, so DTMF and SF
+    //are decimated out of the fast stream first, and each capture holds DTMF_BLOCKS of them back to back
+    const DTMF_FACTOR: usize = 26;
+    const DTMF_TAPS: usize = 256;
+    const DTMF_BUFFER: usize = 1024;
+    const DTMF_BLOCKS: usize = 2;
+    let mut dtmf_decimator: Decimator<DTMF_TAPS> = Decimator::new(DTMF_FACTOR, SAMPLE_RATE as f32, 6000.0);
+    //set up a goertzel filter bank for the 16 DTMF frequencies,
+    //rows and columns first, then their second harmonics in the same order
+    const DTMF_ROWS: usize = 0;
+    const DTMF_COLS: usize = 4;
+    const DTMF_HARMONICS: usize = 8;
+    const DTMF_TONES: usize = 16;
+    let dtmf_freqs = [
         DtmfFreqs::ROW_A, DtmfFreqs::ROW_B, DtmfFreqs::ROW_C, DtmfFreqs::ROW_D,
         DtmfFreqs::COL_1, DtmfFreqs::COL_2, DtmfFreqs::COL_3, DtmfFreqs::COL_A,
+        DtmfHarmonics::ROW_A, DtmfHarmonics::ROW_B, DtmfHarmonics::ROW_C, DtmfHarmonics::ROW_D,
+        DtmfHarmonics::COL_1, DtmfHarmonics::COL_2, DtmfHarmonics::COL_3, DtmfHarmonics::COL_A,
     ];
-    let filters: HashMap<FreqKey, Filter> = freqs.map(|curFreq|
-        (
-            FreqKey::from(curFreq),
-            Filter::new(curFreq, SAMPLE_RATE as f32),
-        )
-    ).iter().cloned().collect();
+    //a windowed block that keeps the 8 tones from leaking into each other, and short enough for a
+    //40ms digit to fill one. The row harmonics sit too close to some columns for any block that short,
+    //so the decoder only checks them against the columns they are clear of
+    const DTMF_PLAN: PlanConstraints = PlanConstraints {
+        max_len: (DTMF_BUFFER - DTMF_TAPS / DTMF_FACTOR - 1) / DTMF_BLOCKS,
+        max_latency: 0.03,
+        max_leakage_db: -30.0,
+        window: WindowKind::Hann,
+        ..PlanConstraints::DEFAULT
+    };
+    let dtmf_len = plan(&dtmf_freqs[DTMF_ROWS..DTMF_HARMONICS], dtmf_decimator.output_rate(), &DTMF_PLAN)
+        .expect("no block length separates the DTMF tones").len;
+    let dtmf_window: Window<DTMF_BUFFER> = Window::with_len(DTMF_PLAN.window, dtmf_len);
+    //the fast samples the DTMF blocks are decimated from, including the decimator's warm-up
+    let dtmf_span = DTMF_BLOCKS * dtmf_len * DTMF_FACTOR + DTMF_TAPS;
+    let mut dtmf_filters: FilterBank<DTMF_TONES> = FilterBank::new(&dtmf_freqs, dtmf_decimator.output_rate());
+    //and one for the SF tone, from the same decimated block, whose whole energy is its guard band
+    const SF: usize = 0;
+    const SF_TONES: usize = 1;
+    let mut sf_filters: FilterBank<SF_TONES> = FilterBank::new(&[SfFreqs::TONE], dtmf_decimator.output_rate());
+    //and one for the buttons of whichever remote is selected; dial *<n># to pick the nth of RemoteProfiles::ALL,
+    //or *0# to calibrate it one button at a time
+    let mut remote = RemoteProfiles::ZENITH_SPACE_COMMAND;
+    let mut remote_filters = remote.filter_bank(SAMPLE_RATE as f32);
+    let mut calibrator: Option<Calibrator> = None;
+    let mut calibrated: Option<CalibratedRemote> = None;
+    let mut remote_tracker = RemoteButtonTracker::new(RemoteTiming::STANDARD);
+    //which buttons are down, from the tracker's Pressed until its Released
+    let mut remote_held = [false; MAX_REMOTE_BUTTONS];
+    //a trigger only counts once the tone has struck and started to ring down
+    let mut strikes = StrikeValidator::new(StrikeCriteria::STANDARD);
+    let dtmf_decoder = DtmfDecoder::new(DtmfCriteria::STANDARD);
+    let mut dtmf_sequencer = DtmfSequencer::new(DtmfTiming::STANDARD);
+    let mut sf_detector = SfDetector::new(SfCriteria::STANDARD, SfTiming::STANDARD);
+    let mut events = EventQueue::new();
+    //put the remote's tones as close to their bins as a block of up to BUFFER_SIZE allows,
+    //keeping the loop at around 200 blocks per second
+    const REMOTE_PLAN: PlanConstraints = PlanConstraints {
+        min_len: BUFFER_SIZE / 2,
+        max_len: BUFFER_SIZE,
+        max_latency: 0.005,
+        //the remote tones are only ~1kHz apart, so the block is windowed to keep them from leaking into each other
+        window: WindowKind::Hann,
+        ..PlanConstraints::DEFAULT
+    };
+    let block_len = |profile: &RemoteProfile| profile.plan(SAMPLE_RATE as f32, &REMOTE_PLAN).map_or(BUFFER_SIZE, |p| p.len);
+    let mut remote_len = block_len(&remote);
+    //each capture is a whole number of remote blocks, the last dtmf_span samples of which make the DTMF block
+    let capture_for = |remote_len: usize| dtmf_span.div_ceil(remote_len) * remote_len;
+    //rounding up to whole remote blocks adds less than one block
+    assert!(dtmf_span + BUFFER_SIZE <= CAPTURE_SIZE);
+    let mut window: Window<BUFFER_SIZE> = Window::with_len(REMOTE_PLAN.window, remote_len);
+    //retune the filters when the polling loop's sample rate drifts more than this
+    const RATE_TOLERANCE: f32 = 0.002;
+    let mut sample_rate = SampleRateMeter::new(SAMPLE_RATE as f32, ccdr.clocks.c_ck().0, 0.25);
+    let cycles_per_us = ccdr.clocks.c_ck().0 / 1_000_000;
+    let mut last_start = DWT::get_cycle_count();
+    //triggers are judged against each frequency's own noise floor rather than an absolute power.
+    //a DTMF block is as long as ~6 remote blocks, so its floors move ~6 times as far per block
+    //and hold a tone off for ~6 times fewer
+    let mut dtmf_floors = [NoiseFloor::new(0.06, 0.06, 30); DTMF_TONES];
+    let mut remote_floors = [NoiseFloor::DEFAULT; MAX_REMOTE_BUTTONS];
 
     let mut adc1_ref_pot = pins.SEED_PIN_15.into_analog();
     let mut bit = false;
@@ -174,21 +283,34 @@ fn main() -> ! {
 
     // - main loop ------------------------------------------------------------
     let _one_second = ccdr.clocks.sys_ck().0;
-    let mut pcm_buffer: [i16; BUFFER_SIZE] = [0; BUFFER_SIZE];
-    let mut fbuf: [f32; BUFFER_SIZE] = [0f32; BUFFER_SIZE];
+    let mut pcm_buffer: [i16; CAPTURE_SIZE] = [0; CAPTURE_SIZE];
+    let mut dtmf_buffer: [f32; DTMF_BUFFER] = [0f32; DTMF_BUFFER];
     loop {
         //load the buffer manually
         //getting about 460kHz (SAMPLE_RATE) with what we do in this loop
         // (36MHz adc_ker_ck_input * 80 clock cycles per iteration)
-        for i in 0..BUFFER_SIZE
+        let start = DWT::get_cycle_count();
+        //the whole trip around the loop, for the timing of the detectors
+        let elapsed_us = start.wrapping_sub(last_start) / cycles_per_us;
+        last_start = start;
+        //one unbroken capture, since the time spent processing it goes unsampled
+        let capture_len = capture_for(remote_len);
+        for i in 0..capture_len
         {
             test_bit.toggle();
             let raw: u32 = adc1.read(&mut adc1_ref_pot).unwrap();
             pcm_buffer[i] = raw as i16 - SCALE_FACTOR;
-            fbuf[i] = raw as f32 - SCALE_FACTOR as f32
         }
-        let mut max: i16 = pcm_buffer.iter().max().unwrap_or(&0).clone();
-        let mut min: i16 = pcm_buffer.iter().min().unwrap_or(&0).clone();
+        let cycles = DWT::get_cycle_count().wrapping_sub(start);
+        let capture = &pcm_buffer[..capture_len];
+        let measured_rate = sample_rate.measure(cycles, capture_len);
+        dtmf_decimator.retune(measured_rate);
+        dtmf_filters.track(dtmf_decimator.output_rate(), RATE_TOLERANCE);
+        sf_filters.track(dtmf_decimator.output_rate(), RATE_TOLERANCE);
+        remote_filters.track(measured_rate, RATE_TOLERANCE);
+        // loggit!("Sample rate:{:?}", sample_rate.last());
+        let mut max: i16 = capture.iter().max().unwrap_or(&0).clone();
+        let mut min: i16 = capture.iter().min().unwrap_or(&0).clone();
         let raw_volume = max - min;
         let volume = (ease_out(raw_volume as f32, 0f32, 3f32, 255f32) + 0.002f32) as u8;
 
@@ -217,24 +339,68 @@ fn main() -> ! {
 
 
         //calc goertzel frequencies
-        let mut filter_results = HashMap::new();
-        for (freq, mut filter) in &filters {
-            filter_results.insert(freq, filter.clone().process(&fbuf));
+        //DTMF and SF get the blocks decimated from the capture's tail after the decimator has settled,
+        //the first one also covering the time the loop was away
+        let capture_us = cycles / cycles_per_us;
+        let dtmf_block_us = (capture_us as u64 * (dtmf_len * DTMF_FACTOR) as u64 / capture_len as u64) as u32;
+        let mut dtmf_elapsed_us = elapsed_us.saturating_sub((DTMF_BLOCKS - 1) as u32 * dtmf_block_us);
+        dtmf_decimator.reset();
+        let decimated = dtmf_decimator.process_q15(&capture[capture_len - dtmf_span..], &mut dtmf_buffer);
+        let mut dtmf_powers = [0f32; DTMF_TONES];
+        let mut dtmf_digit = None;
+        for dtmf_block in dtmf_buffer[decimated - DTMF_BLOCKS * dtmf_len..decimated].chunks_exact(dtmf_len) {
+            dtmf_powers = dtmf_filters.process_windowed(dtmf_block, &dtmf_window);
+            let sf_powers = sf_filters.process_windowed(dtmf_block, &dtmf_window);
+            let mean_square = dtmf_block.iter().map(|x| x * x).sum::<f32>() / dtmf_len as f32;
+            dtmf_digit = dtmf_decoder.decode(&dtmf_powers[DTMF_ROWS..DTMF_HARMONICS], &dtmf_powers[DTMF_HARMONICS..DTMF_TONES]);
+            dtmf_sequencer.update(dtmf_digit.as_ref().map(|d| d.button.to_char()), dtmf_elapsed_us, dtmf_block_us, &mut events);
+            sf_detector.update(sf_powers[SF], mean_square, dtmf_elapsed_us, &mut events);
+            for (floor, power) in dtmf_floors.iter_mut().zip(dtmf_powers.iter()) {
+                floor.update_quiet(*power, STD_SNR_DB);
+            }
+            dtmf_elapsed_us = dtmf_block_us;
+        }
+
+        //the remote gets every block of the capture, the first one also covering the time the loop was away
+        let block_us = capture_us / (capture_len / remote_len) as u32;
+        let mut block_elapsed_us = elapsed_us.saturating_sub(capture_us) + block_us;
+        let mut remote_level = [0u8; MAX_REMOTE_BUTTONS];
+        for pcm in capture.chunks_exact(remote_len) {
+            let remote_powers = remote_filters.process_q15_windowed(pcm, &window);
+            //switch over to the tuned buttons once calibration has measured every one,
+            //leaving the rest of the capture, which was cut for the old profile
+            if let Some(cal) = calibrator.as_mut() {
+                if cal.update(pcm, &window, measured_rate) == CalibrationStep::Done {
+                    let tuned = cal.result().clone();
+                    remote_filters = tuned.profile().filter_bank(remote_filters.sample_rate());
+                    remote_len = block_len(&tuned.profile());
+                    window = Window::with_len(REMOTE_PLAN.window, remote_len);
+                    remote_floors = [NoiseFloor::DEFAULT; MAX_REMOTE_BUTTONS];
+                    calibrated = Some(tuned);
+                    calibrator = None;
+                    remote_tracker.reset();
+                    remote_held = [false; MAX_REMOTE_BUTTONS];
+                    strikes.reset();
+                    break;
+                }
+            }
+            strikes.update(&remote_powers, block_elapsed_us, block_us);
+            let active = match &calibrated {
+                Some(tuned) => tuned.profile(),
+                None => remote.clone(),
+            };
+            let mut remote_triggered = [false; MAX_REMOTE_BUTTONS];
+            for (idx, btn) in active.evaluate(&remote_powers[..active.len()]).enumerate() {
+                remote_level[idx] = remote_level[idx].max(btn.display_range());
+                remote_triggered[idx] = btn.triggered_strike(&remote_floors[idx], STD_SNR_DB, pcm, measured_rate, &mut strikes, idx);
+            }
+            remote_tracker.update(&active, &remote_triggered[..active.len()], block_elapsed_us, &mut events);
+            //only fold this block into the floors after judging it, so a tone can't raise its own floor first
+            for (floor, power) in remote_floors.iter_mut().zip(remote_powers[..active.len()].iter()) {
+                floor.update_quiet(*power, STD_SNR_DB);
+            }
+            block_elapsed_us = block_us;
         }
-        let remote_buttons = [
-            RemoteButtonEval::new(
-                RemoteSignals::CHANNEL_DN,
-                *filter_results.get(&FreqKey::from(RemFreqs::CHANNEL_DN)).unwrap_or(&0f32)),
-            RemoteButtonEval::new(
-                RemoteSignals::VOLUME,
-                *filter_results.get(&FreqKey::from(RemFreqs::VOLUME)).unwrap_or(&0f32)),
-            RemoteButtonEval::new(
-                RemoteSignals::OFF_ON,
-                *filter_results.get(&FreqKey::from(RemFreqs::OFF_ON)).unwrap_or(&0f32)),
-            RemoteButtonEval::new(
-                RemoteSignals::CHANNEL_UP,
-                *filter_results.get(&FreqKey::from(RemFreqs::CHANNEL_UP)).unwrap_or(&0f32)),
-        ];
 
         test_bit.toggle();
         if bit {
@@ -244,117 +410,61 @@ fn main() -> ! {
         }
         bit = !bit;
 
-        //ok this got too confusing for me and the compiler i think
-        // let buttons = [
-        //     DtmfSignals::_1, DtmfSignals::_2, DtmfSignals::_3, DtmfSignals::_A,
-        //     DtmfSignals::_4, DtmfSignals::_5, DtmfSignals::_6, DtmfSignals::_B,
-        //     DtmfSignals::_7, DtmfSignals::_8, DtmfSignals::_9, DtmfSignals::_C,
-        //     DtmfSignals::_STAR, DtmfSignals::_0, DtmfSignals::_POUND, DtmfSignals::_D,
-        // ];
-        // let rows = [DtmfFreqs::ROW_A, DtmfFreqs::ROW_B, DtmfFreqs::ROW_C, DtmfFreqs::ROW_D, ];
-        // let cols = [DtmfFreqs::COL_1, DtmfFreqs::COL_2, DtmfFreqs::COL_3, DtmfFreqs::COL_A, ];
-        // let mut b: usize = 0;
-        // let dtmf_keypad:[[DtmfButtonEval; 4]; 4] = rows.map(|r| {
-        //     b = b + 1;
-        //     cols.map(|c|
-        //         DtmfButtonEval::new(buttons[b-1].clone(),
-        //                             *filter_results.get(&FreqKey::from(r)).unwrap_or(&0f32),
-        //                             *filter_results.get(&FreqKey::from(c)).unwrap_or(&0f32))
-        //     ).iter().cloned().collect()
-        // }).iter().cloned().collect();
-
-        let dtmf_keypad = [
-            [
-                DtmfButtonEval::new(DtmfSignals::_STAR,
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::ROW_D)).unwrap_or(&0f32),
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::COL_1)).unwrap_or(&0f32)),
-                DtmfButtonEval::new(DtmfSignals::_0,
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::ROW_D)).unwrap_or(&0f32),
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::COL_2)).unwrap_or(&0f32)),
-                DtmfButtonEval::new(DtmfSignals::_POUND,
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::ROW_D)).unwrap_or(&0f32),
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::COL_3)).unwrap_or(&0f32)),
-                DtmfButtonEval::new(DtmfSignals::_D,
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::ROW_D)).unwrap_or(&0f32),
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::COL_A)).unwrap_or(&0f32)),
-            ], [
-                DtmfButtonEval::new(DtmfSignals::_7,
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::ROW_C)).unwrap_or(&0f32),
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::COL_1)).unwrap_or(&0f32)),
-                DtmfButtonEval::new(DtmfSignals::_8,
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::ROW_C)).unwrap_or(&0f32),
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::COL_2)).unwrap_or(&0f32)),
-                DtmfButtonEval::new(DtmfSignals::_9,
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::ROW_C)).unwrap_or(&0f32),
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::COL_3)).unwrap_or(&0f32)),
-                DtmfButtonEval::new(DtmfSignals::_C,
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::ROW_C)).unwrap_or(&0f32),
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::COL_A)).unwrap_or(&0f32)),
-            ], [
-                DtmfButtonEval::new(DtmfSignals::_4,
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::ROW_B)).unwrap_or(&0f32),
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::COL_1)).unwrap_or(&0f32)),
-                DtmfButtonEval::new(DtmfSignals::_5,
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::ROW_B)).unwrap_or(&0f32),
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::COL_2)).unwrap_or(&0f32)),
-                DtmfButtonEval::new(DtmfSignals::_6,
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::ROW_B)).unwrap_or(&0f32),
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::COL_3)).unwrap_or(&0f32)),
-                DtmfButtonEval::new(DtmfSignals::_B,
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::ROW_B)).unwrap_or(&0f32),
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::COL_A)).unwrap_or(&0f32)),
-            ], [
-                DtmfButtonEval::new(DtmfSignals::_1,
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::ROW_A)).unwrap_or(&0f32),
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::COL_1)).unwrap_or(&0f32)),
-                DtmfButtonEval::new(DtmfSignals::_2,
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::ROW_A)).unwrap_or(&0f32),
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::COL_2)).unwrap_or(&0f32)),
-                DtmfButtonEval::new(DtmfSignals::_3,
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::ROW_A)).unwrap_or(&0f32),
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::COL_3)).unwrap_or(&0f32)),
-                DtmfButtonEval::new(DtmfSignals::_A,
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::ROW_A)).unwrap_or(&0f32),
-                                    *filter_results.get(&FreqKey::from(DtmfFreqs::COL_A)).unwrap_or(&0f32)),
-            ]
-        ];
+        let dtmf_keypad = DtmfKeypad::evaluate(&dtmf_powers[DTMF_ROWS..DTMF_HARMONICS]);
 
         //--- display updates
 
         led_matrix.clear_display_buffer();
 
+        //the keypad is drawn in the bottom half with its top row (ROW_A) at the bottom
         let mut r = 0u8;
         let mut c = 0u8;
         for row in dtmf_keypad {
             for key in row {
-                if key.either_triggered() {
-                    led_matrix.update_bicolor_led(c, r + 4, Color::Green);
-                }
-                if key.triggered() {
-                    led_matrix.update_bicolor_led(c, r + 4, Color::Red);
+                let row_floor = &dtmf_floors[DTMF_ROWS + r as usize];
+                let col_floor = &dtmf_floors[DTMF_COLS + c as usize];
+                if key.either_triggered_snr(row_floor, col_floor, STD_SNR_DB) {
+                    led_matrix.update_bicolor_led(c, 7 - r, Color::Green);
                 }
                 c = c + 1;
             }
             r = r + 1;
             c = 0u8;
         }
+        if let Some(digit) = &dtmf_digit {
+            led_matrix.update_bicolor_led(digit.col as u8, 7 - digit.row as u8, Color::Red);
+        }
 
-        let mut col = 4u8;
-        let mut idx: usize = 0;
-        for btn in remote_buttons {
-            let curpwr = btn.display_range();
+        //one bar per remote button in columns 4-7, split into a lower and upper half past 4 buttons,
+        //showing the loudest block of the capture
+        let active = match &calibrated {
+            Some(tuned) => tuned.profile(),
+            None => remote.clone(),
+        };
+        let bands = active.len().div_ceil(4).max(1) as u8;
+        let band_height = 8 / bands;
+        let asking_for = match calibrator.as_ref().map(|cal| cal.step()) {
+            Some(CalibrationStep::Listening { button, .. }) => Some(button),
+            _ => None,
+        };
+        for idx in 0..active.len() {
+            let col = 4 + (idx % 4) as u8;
+            let base = (idx / 4) as u8 * band_height;
+            let curpwr = remote_level[idx] * band_height / 8;
             for k in 0..curpwr {
-                led_matrix.update_bicolor_led(col, k, Color::Green);
+                led_matrix.update_bicolor_led(col, base + k, Color::Green);
             }
             if (curpwr > 3) {
-                led_matrix.update_bicolor_led(col, curpwr - 1, Color::Yellow);
-                led_matrix.update_bicolor_led(col, curpwr, Color::Red);
+                led_matrix.update_bicolor_led(col, base + curpwr - 1, Color::Yellow);
+                led_matrix.update_bicolor_led(col, base + curpwr, Color::Red);
+            }
+            //during calibration the button to strike next is marked in yellow
+            if asking_for == Some(idx) {
+                led_matrix.update_bicolor_led(col, base + band_height - 1, Color::Yellow);
             }
-            if btn.triggered() {
-                led_matrix.update_bicolor_led(col, 7, Color::Red);
+            if remote_held[idx] {
+                led_matrix.update_bicolor_led(col, base + band_height - 1, Color::Red);
             }
-            col = col + 1;
-            idx = idx + 1;
         }
 
 
@@ -367,6 +477,43 @@ fn main() -> ! {
         }
 
         led_matrix.write_display_buffer().unwrap();
+
+        //--- event output
+        while let Some(event) = events.pop() {
+            // loggit!("Event:{:?}", event);
+            if let Event::Remote { button, action, .. } = event {
+                match action {
+                    RemoteAction::Pressed => remote_held[button] = true,
+                    RemoteAction::Released => remote_held[button] = false,
+                    _ => {}
+                }
+            }
+            if let Event::NumberDialed(number) = event {
+                let code = number.as_str().strip_prefix('*').and_then(|n| n.parse::<usize>().ok());
+                let selected = match code {
+                    Some(0) => {
+                        calibrator = Some(Calibrator::new(remote.clone()));
+                        Some(remote.clone())
+                    }
+                    Some(n) => {
+                        calibrator = None;
+                        RemoteProfiles::ALL.get(n - 1).cloned()
+                    }
+                    None => None,
+                };
+                if let Some(profile) = selected {
+                    remote = profile;
+                    calibrated = None;
+                    remote_filters = remote.filter_bank(remote_filters.sample_rate());
+                    remote_len = block_len(&remote);
+                    window = Window::with_len(REMOTE_PLAN.window, remote_len);
+                    remote_floors = [NoiseFloor::DEFAULT; MAX_REMOTE_BUTTONS];
+                    remote_tracker.reset();
+                    remote_held = [false; MAX_REMOTE_BUTTONS];
+                    strikes.reset();
+                }
+            }
+        }
         ctr = ctr + 1;
     }
 }
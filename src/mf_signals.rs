@@ -0,0 +1,199 @@
+use crate::dtmf_signals::ButtonFrequency;
+use crate::goertzel::db;
+
+//the six MF R1 trunk signalling tones, every signal is two of them
+pub struct MfFreqs;
+
+impl MfFreqs {
+    pub const F700: f32 = 700f32;
+    pub const F900: f32 = 900f32;
+    pub const F1100: f32 = 1100f32;
+    pub const F1300: f32 = 1300f32;
+    pub const F1500: f32 = 1500f32;
+    pub const F1700: f32 = 1700f32;
+    //in the order MfDecoder::decode expects their powers
+    pub const ALL: [f32; 6] = [
+        MfFreqs::F700, MfFreqs::F900, MfFreqs::F1100, MfFreqs::F1300, MfFreqs::F1500, MfFreqs::F1700,
+    ];
+}
+
+#[derive(Clone)]
+pub struct MfSignal<'a> {
+    pub name: &'a str,
+    pub short_name: &'a str,
+    pub low_freq: ButtonFrequency,
+    pub high_freq: ButtonFrequency,
+}
+
+//MF R1 receivers accept +-1.5% +-5Hz
+const STD_THRESHOLD: f32 = 1f32;
+const HALF_BANDWIDTH: f32 = 30f32;
+
+const fn tone(frequency: f32) -> ButtonFrequency {
+    ButtonFrequency {
+        frequency,
+        power_threshold: STD_THRESHOLD,
+        upper_bandwidth: HALF_BANDWIDTH,
+        lower_bandwidth: HALF_BANDWIDTH,
+    }
+}
+
+#[non_exhaustive]
+pub struct MfSignals;
+
+impl MfSignals {
+    pub const _1: MfSignal<'static> = MfSignal {
+        name: "1",
+        short_name: "1",
+        low_freq: tone(MfFreqs::F700),
+        high_freq: tone(MfFreqs::F900),
+    };
+    pub const _2: MfSignal<'static> = MfSignal {
+        name: "2",
+        short_name: "2",
+        low_freq: tone(MfFreqs::F700),
+        high_freq: tone(MfFreqs::F1100),
+    };
+    pub const _3: MfSignal<'static> = MfSignal {
+        name: "3",
+        short_name: "3",
+        low_freq: tone(MfFreqs::F900),
+        high_freq: tone(MfFreqs::F1100),
+    };
+    pub const _4: MfSignal<'static> = MfSignal {
+        name: "4",
+        short_name: "4",
+        low_freq: tone(MfFreqs::F700),
+        high_freq: tone(MfFreqs::F1300),
+    };
+    pub const _5: MfSignal<'static> = MfSignal {
+        name: "5",
+        short_name: "5",
+        low_freq: tone(MfFreqs::F900),
+        high_freq: tone(MfFreqs::F1300),
+    };
+    pub const _6: MfSignal<'static> = MfSignal {
+        name: "6",
+        short_name: "6",
+        low_freq: tone(MfFreqs::F1100),
+        high_freq: tone(MfFreqs::F1300),
+    };
+    pub const _7: MfSignal<'static> = MfSignal {
+        name: "7",
+        short_name: "7",
+        low_freq: tone(MfFreqs::F700),
+        high_freq: tone(MfFreqs::F1500),
+    };
+    pub const _8: MfSignal<'static> = MfSignal {
+        name: "8",
+        short_name: "8",
+        low_freq: tone(MfFreqs::F900),
+        high_freq: tone(MfFreqs::F1500),
+    };
+    pub const _9: MfSignal<'static> = MfSignal {
+        name: "9",
+        short_name: "9",
+        low_freq: tone(MfFreqs::F1100),
+        high_freq: tone(MfFreqs::F1500),
+    };
+    pub const _0: MfSignal<'static> = MfSignal {
+        name: "0",
+        short_name: "0",
+        low_freq: tone(MfFreqs::F1300),
+        high_freq: tone(MfFreqs::F1500),
+    };
+    //key pulse, starts the address
+    pub const KP: MfSignal<'static> = MfSignal {
+        name: "KP",
+        short_name: "KP",
+        low_freq: tone(MfFreqs::F1100),
+        high_freq: tone(MfFreqs::F1700),
+    };
+    //start, ends the address
+    pub const ST: MfSignal<'static> = MfSignal {
+        name: "ST",
+        short_name: "ST",
+        low_freq: tone(MfFreqs::F1500),
+        high_freq: tone(MfFreqs::F1700),
+    };
+    pub const ST_P: MfSignal<'static> = MfSignal {
+        name: "ST'",
+        short_name: "STP",
+        low_freq: tone(MfFreqs::F900),
+        high_freq: tone(MfFreqs::F1700),
+    };
+    pub const ST_2P: MfSignal<'static> = MfSignal {
+        name: "ST''",
+        short_name: "ST2P",
+        low_freq: tone(MfFreqs::F1300),
+        high_freq: tone(MfFreqs::F1700),
+    };
+    pub const ST_3P: MfSignal<'static> = MfSignal {
+        name: "ST'''",
+        short_name: "ST3P",
+        low_freq: tone(MfFreqs::F700),
+        high_freq: tone(MfFreqs::F1700),
+    };
+    pub const ALL: [MfSignal<'static>; 15] = [
+        MfSignals::_1, MfSignals::_2, MfSignals::_3, MfSignals::_4, MfSignals::_5,
+        MfSignals::_6, MfSignals::_7, MfSignals::_8, MfSignals::_9, MfSignals::_0,
+        MfSignals::KP, MfSignals::ST, MfSignals::ST_P, MfSignals::ST_2P, MfSignals::ST_3P,
+    ];
+}
+
+/// The acceptance criteria for an MF R1 signal
+#[derive(Copy, Clone, Debug)]
+pub struct MfCriteria {
+    /// The weakest either tone may be
+    pub min_power: f32,
+    /// How far apart the two tones' levels may be, in dB
+    pub max_twist_db: f32,
+    /// How far the weaker of the two tones must stand above the third
+    /// strongest, in dB, so exactly two of six are present
+    pub min_third_margin_db: f32,
+}
+
+impl MfCriteria {
+    pub const STANDARD: MfCriteria = MfCriteria {
+        min_power: STD_THRESHOLD,
+        max_twist_db: 6f32,
+        min_third_margin_db: 10f32,
+    };
+}
+
+/// Turns the 6 MF tone powers for a block into at most one MF R1 signal.
+pub struct MfDecoder {
+    pub criteria: MfCriteria,
+}
+
+impl MfDecoder {
+    pub fn new(criteria: MfCriteria) -> MfDecoder {
+        MfDecoder { criteria }
+    }
+
+    /// Decodes one block from the powers of `MfFreqs::ALL`, in that order
+    pub fn decode(&self, tones: &[f32]) -> Option<MfSignal<'static>> {
+        assert_eq!(tones.len(), 6);
+        let c = &self.criteria;
+
+        //the three strongest tones, strongest first
+        let mut order = [0usize, 1, 2, 3, 4, 5];
+        order.sort_unstable_by(|a, b| tones[*b].partial_cmp(&tones[*a]).unwrap_or(core::cmp::Ordering::Equal));
+        let (first, second, third) = (tones[order[0]], tones[order[1]], tones[order[2]]);
+
+        if second < c.min_power {
+            return None;
+        }
+        if db(first / second) > c.max_twist_db {
+            return None;
+        }
+        if db(second / third) < c.min_third_margin_db {
+            return None;
+        }
+
+        let (low, high) = (MfFreqs::ALL[order[0].min(order[1])], MfFreqs::ALL[order[0].max(order[1])]);
+        MfSignals::ALL.iter()
+            .find(|s| s.low_freq.frequency == low && s.high_freq.frequency == high)
+            .cloned()
+    }
+}
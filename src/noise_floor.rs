@@ -0,0 +1,98 @@
+use crate::goertzel::db;
+
+/// How far above its noise floor a tone has to be to count, in dB
+pub const STD_SNR_DB: f32 = 10.0;
+
+/// Tracks the noise floor of one frequency (or band) from block to block.
+///
+/// The floor is a running average of the power readings, so it sits at the
+/// mean noise power and follows a room getting noisier or quieter within a
+/// second or so. A tone would pull it up, so tones, including ones that
+/// are held like a remote button kept down, are kept out of it by feeding
+/// it with `update_quiet`.
+///
+/// Rising much slower than falling would sink the floor under the noise:
+/// a Goertzel bin's power on noise is exponentially distributed, and a
+/// floor that only trusts the quiet readings ends up far under their mean,
+/// where ordinary noise clears any SNR.
+#[derive(Copy, Clone, Debug)]
+pub struct NoiseFloor {
+    /// The current floor, in the same units as the power readings
+    floor: f32,
+    /// How much of a louder reading goes into the floor each block, 0 to 1
+    rise: f32,
+    /// How much of a quieter reading goes into the floor each block, 0 to 1
+    fall: f32,
+    /// False until the first reading has set the floor
+    primed: bool,
+    /// How many readings in a row `update_quiet` has kept out
+    held: u32,
+    /// How many readings in a row `update_quiet` may keep out before it
+    /// takes them for the room getting louder
+    max_hold: u32,
+}
+
+impl NoiseFloor {
+    /// Averages over about half a second at the ~200 blocks per second
+    /// main's remote path runs at, and holds off a tone for a second. Noise
+    /// alone then reads 10dB over it about once in 20000 blocks
+    pub const DEFAULT: NoiseFloor = NoiseFloor::new(0.01, 0.01, 200);
+
+    /// Returns a floor tracker
+    /// * `rise` How much of a louder reading goes into the floor each block
+    /// * `fall` How much of a quieter reading goes into the floor each block
+    /// * `max_hold` How many blocks in a row `update_quiet` may keep out
+    pub const fn new(rise: f32, fall: f32, max_hold: u32) -> NoiseFloor {
+        NoiseFloor {
+            floor: f32::EPSILON,
+            rise,
+            fall,
+            primed: false,
+            held: 0,
+            max_hold,
+        }
+    }
+
+    /// Feeds one block's power reading into the floor.
+    pub fn update(&mut self, power: f32) {
+        if !self.primed {
+            self.floor = power.max(f32::EPSILON);
+            self.primed = true;
+            return;
+        }
+        let rate = if power > self.floor { self.rise } else { self.fall };
+        self.floor = (self.floor + rate * (power - self.floor)).max(f32::EPSILON);
+    }
+
+    /// Feeds one block's power reading into the floor unless it is a tone
+    /// at least `min_snr_db` above it, so a held tone can't raise its own
+    /// floor and drop out while it's still there. A tone that goes on for
+    /// more than `max_hold` blocks is taken to be the room getting louder
+    /// after all, and let in.
+    pub fn update_quiet(&mut self, power: f32, min_snr_db: f32) {
+        if self.above(power, min_snr_db) {
+            if self.held < self.max_hold {
+                self.held += 1;
+                return;
+            }
+        } else {
+            self.held = 0;
+        }
+        self.update(power);
+    }
+
+    /// The current noise floor
+    pub fn floor(&self) -> f32 {
+        self.floor
+    }
+
+    /// How far `power` is above the floor, in dB
+    pub fn snr_db(&self, power: f32) -> f32 {
+        db(power / self.floor)
+    }
+
+    /// True if `power` is at least `min_snr_db` above the floor
+    pub fn above(&self, power: f32, min_snr_db: f32) -> bool {
+        self.primed && self.snr_db(power) >= min_snr_db
+    }
+}
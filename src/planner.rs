@@ -0,0 +1,136 @@
+use libm::{cosf, fabsf, roundf, sinf};
+use crate::goertzel::{db, PI};
+use crate::window::WindowKind;
+
+/// Limits on the block lengths the planner may choose from
+#[derive(Copy, Clone, Debug)]
+pub struct PlanConstraints {
+    /// The shortest block length to consider, in samples
+    pub min_len: usize,
+    /// The longest block length to consider, in samples
+    pub max_len: usize,
+    /// The longest a block may take to capture, in seconds
+    pub max_latency: f32,
+    /// Only consider power-of-two lengths, so the same block can feed an FFT
+    pub power_of_two: bool,
+    /// The most any target tone may leak into another target's filter, in dB
+    pub max_leakage_db: f32,
+    /// The window the block will be run through, which sets how far the tones leak
+    pub window: WindowKind,
+}
+
+impl PlanConstraints {
+    pub const DEFAULT: PlanConstraints = PlanConstraints {
+        min_len: 64,
+        max_len: 8192,
+        max_latency: 0.05,
+        power_of_two: false,
+        max_leakage_db: -20.0,
+        window: WindowKind::Rectangular,
+    };
+}
+
+/// What a block length implies for a set of target frequencies
+#[derive(Copy, Clone, Debug)]
+pub struct BlockPlan {
+    /// The block length, in samples
+    pub len: usize,
+    /// The sampling frequency the plan was made for
+    pub sample_rate: f32,
+    /// The spacing between DFT bins, in Hz
+    pub bin_spacing: f32,
+    /// The furthest any target sits from the nearest bin, as a fraction
+    /// of a bin (0 to 0.5)
+    pub worst_bin_error: f32,
+    /// The most any target tone leaks into another target's filter, in dB
+    pub worst_leakage_db: f32,
+    /// How long a block takes to capture, in seconds
+    pub latency: f32,
+}
+
+/// Returns what a block of `len` samples at `fs`, run through `window`, implies for `freqs`
+pub fn evaluate(freqs: &[f32], fs: f32, len: usize, window: WindowKind) -> BlockPlan {
+    let n = len as f32;
+    let mut worst_bin_error = 0f32;
+    let mut worst_leakage = 0f32;
+    for (i, f) in freqs.iter().enumerate() {
+        let bin = f * n / fs;
+        worst_bin_error = worst_bin_error.max(fabsf(bin - roundf(bin)));
+        for (j, other) in freqs.iter().enumerate() {
+            if i != j {
+                worst_leakage = worst_leakage.max(leakage(*other - *f, fs, len, window));
+            }
+        }
+    }
+    BlockPlan {
+        len,
+        sample_rate: fs,
+        bin_spacing: fs / n,
+        worst_bin_error,
+        worst_leakage_db: db(worst_leakage),
+        latency: n / fs,
+    }
+}
+
+/// Chooses the block length for `freqs` at `fs` that keeps the targets
+/// apart by at least `max_leakage_db` and then puts them as close to bin
+/// centres as possible, preferring the shorter block on a tie.
+/// Returns None if no length within the constraints separates the targets
+pub fn plan(freqs: &[f32], fs: f32, constraints: &PlanConstraints) -> Option<BlockPlan> {
+    let mut best: Option<BlockPlan> = None;
+    let mut len = constraints.min_len.max(1);
+    if constraints.power_of_two {
+        len = len.next_power_of_two();
+    }
+    while len <= constraints.max_len {
+        let candidate = evaluate(freqs, fs, len, constraints.window);
+        if candidate.latency > constraints.max_latency {
+            break;
+        }
+        if candidate.worst_leakage_db <= constraints.max_leakage_db {
+            let better = match best {
+                Some(b) => candidate.worst_bin_error < b.worst_bin_error,
+                None => true,
+            };
+            if better {
+                best = Some(candidate);
+            }
+        }
+        len = if constraints.power_of_two { len * 2 } else { len + 1 };
+    }
+    best
+}
+
+/// The power a unit tone `df` Hz away from a Goertzel filter of `len`
+/// samples through `window` shows up with in that filter, relative to a
+/// tone right on it
+fn leakage(df: f32, fs: f32, len: usize, window: WindowKind) -> f32 {
+    let n = len as f32;
+    let x = 2.0 * PI * df / fs;
+    //a cosine-sum window is a sum of rectangular ones shifted by whole bins either side,
+    //each turned by half a bin's phase
+    let (mut re, mut im) = (0f32, 0f32);
+    for (k, a) in window.coefficients().iter().enumerate() {
+        let shift = 2.0 * PI * k as f32 / n;
+        let (below, above) = (dirichlet(x - shift, n), dirichlet(x + shift, n));
+        let turn = PI * k as f32 / n;
+        if k == 0 {
+            re += a * below;
+        } else {
+            re += 0.5 * a * cosf(turn) * (below + above);
+            im += 0.5 * a * sinf(turn) * (above - below);
+        }
+    }
+    let on = window.coefficients()[0];
+    (re * re + im * im) / (on * on)
+}
+
+/// The response of a rectangular window of `n` samples to a tone `x`
+/// radians per sample away, 1 right on it
+fn dirichlet(x: f32, n: f32) -> f32 {
+    let den = n * sinf(x / 2.0);
+    if fabsf(den) < f32::EPSILON {
+        return 1.0;
+    }
+    sinf(n * x / 2.0) / den
+}
@@ -0,0 +1,135 @@
+use crate::events::{Event, EventQueue};
+use crate::space_command_remote::{RemoteProfile, MAX_REMOTE_BUTTONS};
+
+/// What a remote button did
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RemoteAction {
+    /// Struck, and rang for at least the debounce time
+    Pressed,
+    /// Still ringing `hold_ms` after it was pressed, `duration_ms` in all
+    Held(u32),
+    /// Still held, another `repeat_ms` on; for ramping things like volume
+    Repeat,
+    /// Gone quiet again
+    Released,
+}
+
+/// The timing rules for turning per-block triggers into button actions
+#[derive(Copy, Clone, Debug)]
+pub struct RemoteTiming {
+    /// How long a trigger must last before the button counts as pressed
+    pub debounce_ms: u32,
+    /// How long a button must be quiet before it counts as released;
+    /// shorter dropouts are bridged
+    pub release_ms: u32,
+    /// How long after a release the same button is ignored, so the end
+    /// of a ring-down can't press it again
+    pub refractory_ms: u32,
+    /// How long a button must stay down to count as held
+    pub hold_ms: u32,
+    /// How often a held button repeats
+    pub repeat_ms: u32,
+}
+
+impl RemoteTiming {
+    pub const STANDARD: RemoteTiming = RemoteTiming {
+        debounce_ms: 10,
+        release_ms: 40,
+        refractory_ms: 150,
+        hold_ms: 600,
+        repeat_ms: 200,
+    };
+}
+
+#[derive(Copy, Clone, Debug)]
+enum State {
+    Idle,
+    /// Triggered since `since`, but not yet for long enough
+    Pending { since: u64 },
+    /// Pressed since `since`; `gap` is when it last went quiet, and
+    /// `next` when it next holds or repeats
+    Down { since: u64, gap: Option<u64>, held: bool, next: u64 },
+    /// Released, and deaf until `until`
+    Refractory { until: u64 },
+}
+
+/// Debounces each remote button's per-block trigger into `Remote` events,
+/// so one strike is exactly one `Pressed` and one `Released`.
+pub struct RemoteButtonTracker {
+    pub timing: RemoteTiming,
+    states: [State; MAX_REMOTE_BUTTONS],
+    /// Microseconds since the tracker started
+    now: u64,
+}
+
+impl RemoteButtonTracker {
+    pub fn new(timing: RemoteTiming) -> RemoteButtonTracker {
+        RemoteButtonTracker {
+            timing,
+            states: [State::Idle; MAX_REMOTE_BUTTONS],
+            now: 0,
+        }
+    }
+
+    /// Forgets every button, for when the profile changes
+    pub fn reset(&mut self) {
+        self.states = [State::Idle; MAX_REMOTE_BUTTONS];
+    }
+
+    /// Feeds in the latest block.
+    /// * `triggered` Whether each of `profile`'s buttons triggered, in order
+    /// * `elapsed_us` How long the block took, in microseconds
+    pub fn update(&mut self, profile: &RemoteProfile, triggered: &[bool], elapsed_us: u32, events: &mut EventQueue) {
+        let block_start = self.now;
+        self.now += elapsed_us as u64;
+        let now = self.now;
+        let ms = |ms: u32| ms as u64 * 1000;
+        let t = self.timing;
+
+        for (button, (state, on)) in self.states.iter_mut().zip(triggered.iter()).enumerate() {
+            let name = match profile.buttons().get(button) {
+                Some(signal) => signal.short_name,
+                None => break,
+            };
+            let mut emit = |action| events.push(Event::Remote { button, name, action });
+
+            *state = match (*state, *on) {
+                (State::Idle, false) => State::Idle,
+                (State::Idle, true) => State::Pending { since: block_start },
+                (State::Pending { since }, true) => {
+                    if now - since >= ms(t.debounce_ms) {
+                        emit(RemoteAction::Pressed);
+                        State::Down { since, gap: None, held: false, next: since + ms(t.hold_ms) }
+                    } else {
+                        State::Pending { since }
+                    }
+                }
+                //too short to be a strike
+                (State::Pending { .. }, false) => State::Idle,
+                (State::Down { since, held, next, .. }, true) => {
+                    if now < next {
+                        State::Down { since, gap: None, held, next }
+                    } else if held {
+                        emit(RemoteAction::Repeat);
+                        State::Down { since, gap: None, held, next: next + ms(t.repeat_ms) }
+                    } else {
+                        emit(RemoteAction::Held(((now - since) / 1000) as u32));
+                        State::Down { since, gap: None, held: true, next: next + ms(t.repeat_ms) }
+                    }
+                }
+                (State::Down { since, gap, held, next }, false) => {
+                    let gap = gap.unwrap_or(block_start);
+                    if now - gap >= ms(t.release_ms) {
+                        emit(RemoteAction::Released);
+                        State::Refractory { until: gap + ms(t.refractory_ms) }
+                    } else {
+                        State::Down { since, gap: Some(gap), held, next }
+                    }
+                }
+                (State::Refractory { until }, _) if now < until => State::Refractory { until },
+                (State::Refractory { .. }, false) => State::Idle,
+                (State::Refractory { .. }, true) => State::Pending { since: block_start },
+            };
+        }
+    }
+}
@@ -0,0 +1,49 @@
+/// Measures the real sample rate of each captured block from a cycle
+/// count, for retuning filters as the polling loop drifts.
+#[derive(Copy, Clone, Debug)]
+pub struct SampleRateMeter {
+    /// The frequency of the clock the cycles are counted on
+    clock_hz: f32,
+    /// How much of each new measurement goes into the smoothed rate, 0 to 1
+    smoothing: f32,
+    /// The rate measured for the last block
+    last: f32,
+    /// The smoothed rate
+    rate: f32,
+}
+
+impl SampleRateMeter {
+    /// Returns a meter starting out at `nominal` samples per second
+    /// * `clock_hz` The frequency of the clock the cycles are counted on
+    /// * `smoothing` How much of each new measurement goes into the
+    ///   smoothed rate, 1 meaning no smoothing
+    pub fn new(nominal: f32, clock_hz: u32, smoothing: f32) -> SampleRateMeter {
+        assert!(smoothing > 0.0 && smoothing <= 1.0);
+        SampleRateMeter {
+            clock_hz: clock_hz as f32,
+            smoothing,
+            last: nominal,
+            rate: nominal,
+        }
+    }
+
+    /// Records a block of `samples` that took `cycles` clock cycles to
+    /// capture. Returns the smoothed sample rate
+    pub fn measure(&mut self, cycles: u32, samples: usize) -> f32 {
+        if cycles > 0 {
+            self.last = self.clock_hz * samples as f32 / cycles as f32;
+            self.rate += self.smoothing * (self.last - self.rate);
+        }
+        self.rate
+    }
+
+    /// The smoothed sample rate, in samples per second
+    pub fn rate(&self) -> f32 {
+        self.rate
+    }
+
+    /// The sample rate measured for the last block alone
+    pub fn last(&self) -> f32 {
+        self.last
+    }
+}
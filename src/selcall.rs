@@ -0,0 +1,182 @@
+use libm::fabsf;
+use crate::events::{DialedNumber, Event, EventQueue};
+use crate::goertzel::{strongest, FilterBank};
+
+/// How many tones make up a selcall address
+pub const ADDRESS_LEN: usize = 5;
+
+/// The digit each position of a `SelcallStandard` tone table stands for
+pub const DIGITS: [char; 16] = [
+    '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', 'A', 'B', 'C', 'D', 'E', 'F',
+];
+/// Sent in place of a digit that's the same as the one before it
+pub const REPEAT: char = 'E';
+
+/// A sequential tone signalling scheme: one tone per digit, back to back
+#[derive(Clone, Debug)]
+pub struct SelcallStandard<'a> {
+    pub name: &'a str,
+    /// The tone for each of `DIGITS`
+    pub tones: [f32; 16],
+    /// How long each tone lasts
+    pub tone_ms: u32,
+}
+
+impl SelcallStandard<'_> {
+    /// Returns a Goertzel filter bank with one filter per tone, in the
+    /// order of `DIGITS`, for `SelcallDecoder::update`
+    /// * `fs`  The sampling frequency of the samples to process
+    pub fn filter_bank(&self, fs: f32) -> FilterBank<16> {
+        FilterBank::new(&self.tones, fs)
+    }
+}
+
+#[non_exhaustive]
+pub struct SelcallStandards;
+
+impl SelcallStandards {
+    pub const ZVEI1: SelcallStandard<'static> = SelcallStandard {
+        name: "ZVEI1",
+        tones: [
+            2400.0, 1060.0, 1160.0, 1270.0, 1400.0, 1530.0, 1670.0, 1830.0,
+            2000.0, 2200.0, 2800.0, 810.0, 970.0, 885.0, 2600.0, 680.0,
+        ],
+        tone_ms: 70,
+    };
+    pub const ZVEI2: SelcallStandard<'static> = SelcallStandard {
+        name: "ZVEI2",
+        tones: [
+            2400.0, 1060.0, 1160.0, 1270.0, 1400.0, 1530.0, 1670.0, 1830.0,
+            2000.0, 2200.0, 885.0, 825.0, 740.0, 680.0, 970.0, 2600.0,
+        ],
+        tone_ms: 70,
+    };
+    pub const CCIR: SelcallStandard<'static> = SelcallStandard {
+        name: "CCIR",
+        tones: [
+            1981.0, 1124.0, 1197.0, 1275.0, 1358.0, 1446.0, 1540.0, 1640.0,
+            1747.0, 1860.0, 2400.0, 930.0, 2247.0, 991.0, 2110.0, 2010.0,
+        ],
+        tone_ms: 100,
+    };
+    //CCIR's tones, but A moved and faster
+    pub const EEA: SelcallStandard<'static> = SelcallStandard {
+        name: "EEA",
+        tones: [
+            1981.0, 1124.0, 1197.0, 1275.0, 1358.0, 1446.0, 1540.0, 1640.0,
+            1747.0, 1860.0, 1055.0, 930.0, 2247.0, 991.0, 2110.0, 2010.0,
+        ],
+        tone_ms: 40,
+    };
+}
+
+/// What a block must look like to hold a selcall tone, and how closely
+/// each tone must keep to the standard's length
+#[derive(Copy, Clone, Debug)]
+pub struct SelcallCriteria {
+    /// The weakest a tone may be
+    pub min_power: f32,
+    /// How far the strongest tone must stand above every other, in dB
+    pub min_relative_peak_db: f32,
+    /// How far a tone's length may stray from `tone_ms`, as a fraction
+    pub duration_tolerance: f32,
+}
+
+impl SelcallCriteria {
+    pub const STANDARD: SelcallCriteria = SelcallCriteria {
+        min_power: 1f32,
+        min_relative_peak_db: 6f32,
+        duration_tolerance: 0.4,
+    };
+}
+
+/// Follows the strongest tone of a `SelcallStandard` from block to block,
+/// turns each one of the right length into a digit, and reports a
+/// `Selcall` event once a whole address has come in.
+pub struct SelcallDecoder {
+    pub standard: SelcallStandard<'static>,
+    pub criteria: SelcallCriteria,
+    /// The tone heard in the latest blocks, if any, and since when
+    tone: Option<usize>,
+    since: u64,
+    address: DialedNumber,
+    /// Microseconds since the decoder started
+    now: u64,
+}
+
+impl SelcallDecoder {
+    pub fn new(standard: SelcallStandard<'static>, criteria: SelcallCriteria) -> SelcallDecoder {
+        SelcallDecoder {
+            standard,
+            criteria,
+            tone: None,
+            since: 0,
+            address: DialedNumber::new(),
+            now: 0,
+        }
+    }
+
+    /// The digits of the address heard so far
+    pub fn address(&self) -> &DialedNumber {
+        &self.address
+    }
+
+    /// Which tone, if any, one block's powers hold
+    /// * `tones` The powers of the standard's `tones`, in that order
+    pub fn detect(&self, tones: &[f32]) -> Option<usize> {
+        assert_eq!(tones.len(), 16);
+        let best = strongest(tones, self.criteria.min_relative_peak_db)?;
+        if tones[best] < self.criteria.min_power {
+            return None;
+        }
+        Some(best)
+    }
+
+    /// Feeds in the latest block.
+    /// * `tones` The powers of the standard's `tones`, in that order
+    /// * `elapsed_us` How long the block took, in microseconds
+    pub fn update(&mut self, tones: &[f32], elapsed_us: u32, events: &mut EventQueue) {
+        let block_start = self.now;
+        self.now += elapsed_us as u64;
+        let tone = self.detect(tones);
+
+        if tone != self.tone {
+            if let Some(ended) = self.tone {
+                let lasted_ms = ((block_start - self.since) / 1000) as u32;
+                self.tone_ended(ended, lasted_ms, events);
+            }
+            self.tone = tone;
+            self.since = block_start;
+        }
+
+        //the tones of an address follow straight on from each other, so a gap ends it
+        let quiet_ms = ((self.now - self.since) / 1000) as u32;
+        if self.tone.is_none() && quiet_ms > self.standard.tone_ms {
+            self.address.clear();
+        }
+    }
+
+    fn tone_ended(&mut self, index: usize, lasted_ms: u32, events: &mut EventQueue) {
+        let nominal = self.standard.tone_ms as f32;
+        if fabsf(lasted_ms as f32 - nominal) > nominal * self.criteria.duration_tolerance {
+            self.address.clear();
+            return;
+        }
+        let digit = match DIGITS[index] {
+            REPEAT => match self.address.as_str().chars().last() {
+                Some(previous) => previous,
+                None => {
+                    //nothing to repeat
+                    self.address.clear();
+                    return;
+                }
+            },
+            digit => digit,
+        };
+        self.address.push(digit);
+        if self.address.len() == ADDRESS_LEN {
+            events.push(Event::Selcall(self.address));
+            self.address.clear();
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use crate::events::{Event, EventQueue};
+use crate::goertzel::db;
+
+//the single-frequency supervisory tone: present while a trunk is idle (on-hook), gone while it's in use
+pub struct SfFreqs;
+
+impl SfFreqs {
+    pub const TONE: f32 = 2600f32;
+}
+
+/// What a block must look like to count as SF tone
+#[derive(Copy, Clone, Debug)]
+pub struct SfCriteria {
+    /// The weakest the 2600Hz tone may be
+    pub min_power: f32,
+    /// How far the tone must stand above the rest of the block's energy, in dB.
+    /// The whole band is the guard: speech spreads its energy across it, a
+    /// clean SF tone doesn't
+    pub min_guard_rejection_db: f32,
+}
+
+impl SfCriteria {
+    pub const STANDARD: SfCriteria = SfCriteria {
+        min_power: 1f32,
+        //a tone half a bin off loses ~1.4dB to main's Hann window, which the rest of the
+        //block then holds, leaving it only ~4dB clear even with no noise at all
+        min_guard_rejection_db: 3f32,
+    };
+}
+
+/// The minimum-duration rules for changing the line state
+#[derive(Copy, Clone, Debug)]
+pub struct SfTiming {
+    /// How long the tone must be heard before the trunk counts as released;
+    /// long enough that a whistle in speech getting past the guard won't do it
+    pub min_on_ms: u32,
+    /// How long the tone must be gone before the trunk counts as seized
+    pub min_off_ms: u32,
+}
+
+impl SfTiming {
+    pub const STANDARD: SfTiming = SfTiming {
+        min_on_ms: 300,
+        min_off_ms: 60,
+    };
+}
+
+/// Watches the 2600Hz tone and reports `Release` (on-hook) when it appears
+/// and `Seize` (off-hook) when it goes away, once each has lasted long enough.
+pub struct SfDetector {
+    pub criteria: SfCriteria,
+    pub timing: SfTiming,
+    /// Whether the trunk was last seen idle, or None until a tone or silence has lasted long enough to tell
+    idle: Option<bool>,
+    /// Whether the latest blocks held tone, and since when
+    tone: bool,
+    since: u64,
+    /// Microseconds since the detector started
+    now: u64,
+}
+
+impl SfDetector {
+    pub fn new(criteria: SfCriteria, timing: SfTiming) -> SfDetector {
+        SfDetector {
+            criteria,
+            timing,
+            idle: None,
+            tone: false,
+            since: 0,
+            now: 0,
+        }
+    }
+
+    /// Whether the trunk is idle (tone on), or None if it hasn't been decided yet
+    pub fn idle(&self) -> Option<bool> {
+        self.idle
+    }
+
+    /// Whether one block holds SF tone
+    /// * `tone` The power at `SfFreqs::TONE`
+    /// * `mean_square` The mean square of the block's samples, its energy across the band
+    pub fn detect(&self, tone: f32, mean_square: f32) -> bool {
+        //a sine's power is a quarter of its amplitude squared, its mean square half
+        let rest = mean_square - 2f32 * tone;
+        tone >= self.criteria.min_power
+            && db(2f32 * tone / rest) >= self.criteria.min_guard_rejection_db
+    }
+
+    /// Feeds in the latest block.
+    /// * `elapsed_us` How long the block took, in microseconds
+    pub fn update(&mut self, tone: f32, mean_square: f32, elapsed_us: u32, events: &mut EventQueue) {
+        let block_start = self.now;
+        self.now += elapsed_us as u64;
+        let present = self.detect(tone, mean_square);
+        if present != self.tone {
+            self.tone = present;
+            self.since = block_start;
+        }
+
+        let held_ms = ((self.now - self.since) / 1000) as u32;
+        match (self.idle, present) {
+            (Some(true), true) | (Some(false), false) => {}
+            (_, true) if held_ms >= self.timing.min_on_ms => {
+                self.idle = Some(true);
+                events.push(Event::Release);
+            }
+            (_, false) if held_ms >= self.timing.min_off_ms => {
+                //silence at power-up says nothing about the trunk, only losing the tone does
+                if self.idle.is_some() {
+                    events.push(Event::Seize);
+                }
+                self.idle = Some(false);
+            }
+            _ => {}
+        }
+    }
+}
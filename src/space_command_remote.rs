@@ -1,9 +1,14 @@
 use core::cmp::Ordering;
 use libm::sqrtf;
 use ordered_float::OrderedFloat;
-use spectrum_analyzer::{FrequencyLimit, FrequencySpectrum, FrequencyValue};
-use crate::dtmf_signals::ButtonFrequency;
+use spectrum_analyzer::{FrequencyLimit, FrequencyValue};
+use crate::dtmf_signals::{ButtonFrequency, PowerSpectrum};
 use crate::max_pwr_in_range;
+use crate::estimator::{estimate_peak, PeakEstimate};
+use crate::goertzel::FilterBank;
+use crate::noise_floor::NoiseFloor;
+use crate::planner::{plan, BlockPlan, PlanConstraints};
+use crate::strike_envelope::StrikeValidator;
 
 
 pub struct RemoteButtonEval {
@@ -13,7 +18,7 @@ pub struct RemoteButtonEval {
 }
 
 impl RemoteButtonEval {
-    pub fn from_spectrum(remote_button: RemoteButtonSignal<'static>, spectrum: &FrequencySpectrum) -> RemoteButtonEval {
+    pub fn from_spectrum<S: PowerSpectrum>(remote_button: RemoteButtonSignal<'static>, spectrum: &S) -> RemoteButtonEval {
         let power = remote_button.pwr_in_spectrum(spectrum);
         RemoteButtonEval {
             remote_button,
@@ -35,13 +40,37 @@ impl RemoteButtonEval {
     pub fn triggered(&self) -> bool {
         self.power > self.remote_button.freq.power_threshold
     }
+    //like the above, but against an adaptive noise floor instead of the absolute threshold
+    pub fn triggered_snr(&self, floor: &NoiseFloor, min_snr_db: f32) -> bool {
+        floor.above(self.power, min_snr_db)
+    }
+    //like triggered_snr, but the tone must also clear the button's own power threshold and peak
+    //within its bandwidth in `sample`, the windowed block the power was read from, as calibration tunes them
+    pub fn triggered_tuned(&self, floor: &NoiseFloor, min_snr_db: f32, sample: &[i16], fs: f32) -> bool {
+        self.triggered() && self.triggered_snr(floor, min_snr_db) && self.remote_button.in_band(sample, fs)
+    }
+    //like triggered_tuned, but held back until the tone's envelope looks like a rod being struck
+    pub fn triggered_strike(
+        &self,
+        floor: &NoiseFloor,
+        min_snr_db: f32,
+        sample: &[i16],
+        fs: f32,
+        strikes: &mut StrikeValidator,
+        button: usize,
+    ) -> bool {
+        strikes.validate(button, self.triggered_tuned(floor, min_snr_db, sample, fs))
+    }
+    pub fn snr_db(&self, floor: &NoiseFloor) -> f32 {
+        floor.snr_db(self.power)
+    }
     fn ease_out(t: f32, b: f32, c: f32, d: f32) -> f32 {
         let t = t / d - 1f32;
         c * sqrtf(1f32 - t * t) + b
     }
 }
 
-
+#[derive(Clone)]
 pub struct RemoteButtonSignal<'a> {
     pub name: &'a str,
     pub short_name: &'a str,
@@ -49,13 +78,39 @@ pub struct RemoteButtonSignal<'a> {
 }
 
 impl RemoteButtonSignal<'static> {
-    fn pwr_in_spectrum(&self, spectrum: &FrequencySpectrum) -> f32 {
+    fn pwr_in_spectrum<S: PowerSpectrum>(&self, spectrum: &S) -> f32 {
         let limit = FrequencyLimit::Range(
             self.freq.frequency - self.freq.lower_bandwidth,
             self.freq.frequency + self.freq.upper_bandwidth,
         );
         max_pwr_in_range(spectrum, limit)
     }
+
+    /// Estimates where this button's rod actually peaks in one block of
+    /// samples, searching within the button's bandwidth
+    pub fn estimate_peak<S: Copy + Into<f32>>(&self, sample: &[S], fs: f32) -> Option<PeakEstimate> {
+        estimate_peak(
+            sample,
+            self.freq.frequency,
+            fs,
+            self.freq.lower_bandwidth.max(self.freq.upper_bandwidth),
+        )
+    }
+
+    /// Whether the rod peaks within the button's bandwidth in one block of
+    /// samples. The search reaches a bin past the band, so a peak near its
+    /// edge isn't lost for being on the edge of the search
+    pub fn in_band<S: Copy + Into<f32>>(&self, sample: &[S], fs: f32) -> bool {
+        let f = &self.freq;
+        let spacing = fs / sample.len() as f32;
+        estimate_peak(sample, f.frequency, fs, f.lower_bandwidth.max(f.upper_bandwidth) + spacing)
+            .is_some_and(|p| p.frequency >= f.frequency - f.lower_bandwidth && p.frequency <= f.frequency + f.upper_bandwidth)
+    }
+
+    /// Returns how far the rod has drifted from its nominal frequency, in Hz
+    pub fn drift(&self, estimate: &PeakEstimate) -> f32 {
+        estimate.frequency - self.freq.frequency
+    }
 }
 
 const HALF_KHZ: f32 = 500f32;
@@ -116,4 +171,89 @@ impl RemoteSignals {
             lower_bandwidth: HALF_KHZ,
         },
     };
-}
\ No newline at end of file
+}
+
+/// The most buttons a profile can have; one bar each on the LED matrix
+pub const MAX_REMOTE_BUTTONS: usize = 8;
+
+/// One model of ultrasonic remote: its buttons, in the order they are shown
+#[derive(Clone)]
+pub struct RemoteProfile<'a> {
+    pub name: &'a str,
+    buttons: &'a [RemoteButtonSignal<'static>],
+}
+
+impl<'a> RemoteProfile<'a> {
+    /// Returns a profile of the given buttons.
+    /// Panics if there are more than `MAX_REMOTE_BUTTONS`, which for a
+    /// profile made in a `const` stops the build
+    pub const fn new(name: &'a str, buttons: &'a [RemoteButtonSignal<'static>]) -> RemoteProfile<'a> {
+        assert!(buttons.len() <= MAX_REMOTE_BUTTONS, "a remote profile can have at most MAX_REMOTE_BUTTONS buttons");
+        RemoteProfile { name, buttons }
+    }
+
+    /// The buttons, in the order they are shown
+    pub fn buttons(&self) -> &'a [RemoteButtonSignal<'static>] {
+        self.buttons
+    }
+
+    pub fn len(&self) -> usize {
+        self.buttons.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buttons.is_empty()
+    }
+
+    /// The buttons' frequencies in order; slots past `len()` are 0
+    fn frequencies(&self) -> [f32; MAX_REMOTE_BUTTONS] {
+        let mut freqs = [0f32; MAX_REMOTE_BUTTONS];
+        for (f, button) in freqs.iter_mut().zip(self.buttons.iter()) {
+            *f = button.freq.frequency;
+        }
+        freqs
+    }
+
+    /// Returns a Goertzel filter bank with one filter per button, in order
+    /// * `fs`  The sampling frequency of the samples to process
+    pub fn filter_bank(&self, fs: f32) -> FilterBank<MAX_REMOTE_BUTTONS> {
+        FilterBank::new(&self.frequencies()[..self.len()], fs)
+    }
+
+    /// Chooses the block length for the buttons with `planner::plan`
+    /// * `fs`  The sampling frequency of the samples to process
+    pub fn plan(&self, fs: f32, constraints: &PlanConstraints) -> Option<BlockPlan> {
+        plan(&self.frequencies()[..self.len()], fs, constraints)
+    }
+
+    /// Pairs each button with its power from a bank made by `filter_bank`
+    pub fn evaluate<'p>(&'p self, powers: &'p [f32]) -> impl Iterator<Item = RemoteButtonEval> + 'p {
+        self.buttons.iter()
+            .zip(powers.iter())
+            .map(|(button, power)| RemoteButtonEval::new(button.clone(), *power))
+    }
+}
+
+#[non_exhaustive]
+pub struct RemoteProfiles;
+
+impl RemoteProfiles {
+    pub const ZENITH_SPACE_COMMAND: RemoteProfile<'static> = RemoteProfile::new("Zenith Space Command", &[
+        RemoteSignals::CHANNEL_DN,
+        RemoteSignals::VOLUME,
+        RemoteSignals::OFF_ON,
+        RemoteSignals::CHANNEL_UP,
+    ]);
+    /// The built-in profiles, the ones main lets you pick. Only the Zenith
+    /// Space Command's rods have been measured so far; other models go in
+    /// here once theirs have been, since calibration only looks for a rod
+    /// near the frequency it is given
+    pub const ALL: [RemoteProfile<'static>; 1] = [
+        RemoteProfiles::ZENITH_SPACE_COMMAND,
+    ];
+
+    /// The built-in profile with the given name
+    pub fn find(name: &str) -> Option<RemoteProfile<'static>> {
+        RemoteProfiles::ALL.iter().find(|p| p.name == name).cloned()
+    }
+}
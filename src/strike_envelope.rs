@@ -0,0 +1,227 @@
+use libm::{fabsf, sqrtf};
+use crate::goertzel::db;
+use crate::space_command_remote::MAX_REMOTE_BUTTONS;
+
+/// How many points of envelope are kept. They are spaced so the history spans
+/// the longest decay the criteria accept, about 4ms apart for a one second decay
+const HISTORY: usize = 256;
+/// The dB a power falls by over one time constant of an exponential decay, 20 log10(e)
+const DB_PER_TAU: f32 = 8.685_889;
+
+/// What a strike's envelope must look like
+#[derive(Copy, Clone, Debug)]
+pub struct StrikeCriteria {
+    /// How far the peak must rise above the envelope before it, in dB
+    pub min_attack_db: f32,
+    /// How quickly that rise must happen
+    pub max_attack_ms: u32,
+    /// How many envelope points after the peak the decay is judged over
+    pub min_decay_points: usize,
+    /// The shortest and longest time constant of the ring-down, in ms.
+    /// A continuous tone doesn't decay at all, so it falls outside
+    pub min_decay_ms: f32,
+    pub max_decay_ms: f32,
+    /// How far the decay may stray from a straight line in dB, as an RMS.
+    /// A ring-down follows one closely, where noise swells by chance
+    /// scatter around it by 5dB or more
+    pub max_decay_residual_db: f32,
+}
+
+impl StrikeCriteria {
+    pub const STANDARD: StrikeCriteria = StrikeCriteria {
+        min_attack_db: 10f32,
+        max_attack_ms: 10,
+        min_decay_points: 6,
+        min_decay_ms: 5f32,
+        max_decay_ms: 1000f32,
+        max_decay_residual_db: 2f32,
+    };
+}
+
+#[derive(Copy, Clone, Debug)]
+enum State {
+    Idle,
+    /// Triggered at `onset`, and waiting on enough of the decay to judge it;
+    /// `dropped` is when the trigger went away if it already has
+    Judging { onset: u64, dropped: Option<u64> },
+    /// Judged a strike, and passing while the trigger lasts and at least until `until`
+    Passed { until: u64 },
+}
+
+/// Follows the envelope of each remote button's tone block by block and
+/// only lets a trigger through once it has the shape of a rod being struck:
+/// a sharp attack out of the background, then an exponential ring-down with
+/// a time constant in the configured window. Switch-mode supply whine and
+/// other continuous noise never decays, so it never passes.
+pub struct StrikeValidator {
+    pub criteria: StrikeCriteria,
+    /// When each point was taken, in microseconds since the validator started
+    times: [u64; HISTORY],
+    /// Each button's power at each point, in dB
+    levels: [[f32; HISTORY]; MAX_REMOTE_BUTTONS],
+    /// Where the next point goes, and how many there are
+    pos: usize,
+    filled: usize,
+    /// How far apart the points are, in microseconds
+    step_us: u64,
+    states: [State; MAX_REMOTE_BUTTONS],
+    now: u64,
+}
+
+impl StrikeValidator {
+    pub fn new(criteria: StrikeCriteria) -> StrikeValidator {
+        let step_us = (criteria.max_decay_ms * 1000f32 / HISTORY as f32) as u64;
+        //the attack has to be seen over at least two points
+        assert!(2 * step_us <= criteria.max_attack_ms as u64 * 1000);
+        StrikeValidator {
+            criteria,
+            times: [0; HISTORY],
+            levels: [[0f32; HISTORY]; MAX_REMOTE_BUTTONS],
+            pos: 0,
+            filled: 0,
+            step_us,
+            states: [State::Idle; MAX_REMOTE_BUTTONS],
+            now: 0,
+        }
+    }
+
+    /// Forgets every envelope, for when the profile changes
+    pub fn reset(&mut self) {
+        self.filled = 0;
+        self.pos = 0;
+        self.states = [State::Idle; MAX_REMOTE_BUTTONS];
+    }
+
+    /// Adds one block's power at every button to the envelopes. Blocks
+    /// closer together than a point's spacing share the point, which keeps
+    /// the louder of them
+    /// * `powers` The block's windowed powers, as the remote detector reads them
+    /// * `elapsed_us` How long since the last block started
+    /// * `block_us` How long this block took to sample
+    pub fn update(&mut self, powers: &[f32], elapsed_us: u32, block_us: u32) {
+        self.now += elapsed_us as u64;
+        //each block is placed at its middle
+        let at = self.now + block_us as u64 / 2;
+        let last = (self.pos + HISTORY - 1) % HISTORY;
+        if self.filled > 0 && at < self.times[last] + self.step_us {
+            for (level, power) in self.levels.iter_mut().zip(powers.iter()) {
+                level[last] = level[last].max(db(*power));
+            }
+            return;
+        }
+        self.times[self.pos] = at;
+        for (level, power) in self.levels.iter_mut().zip(powers.iter()) {
+            level[self.pos] = db(*power);
+        }
+        self.pos = (self.pos + 1) % HISTORY;
+        self.filled = (self.filled + 1).min(HISTORY);
+    }
+
+    /// Passes on `triggered` for `button` only if its envelope is a strike.
+    /// Judging takes some of the decay, so a strike passes late, but for at
+    /// least as long as its trigger had lasted by then and on until it
+    /// drops. A trigger that drops before it can be judged is judged anyway
+    pub fn validate(&mut self, button: usize, triggered: bool) -> bool {
+        let now = self.now;
+        let state = match (self.states[button], triggered) {
+            (State::Idle, true) => State::Judging { onset: now, dropped: None },
+            (State::Judging { onset, dropped: None }, false) => State::Judging { onset, dropped: Some(now) },
+            (State::Judging { onset, dropped: Some(_) }, true) => State::Judging { onset, dropped: None },
+            (State::Passed { until }, false) if now >= until => State::Idle,
+            (state, _) => state,
+        };
+        self.states[button] = match state {
+            State::Judging { onset, dropped } => match self.is_strike(button, onset) {
+                Some(true) => State::Passed { until: now + (dropped.unwrap_or(now) - onset) },
+                Some(false) if dropped.is_some() => State::Idle,
+                //a trigger still on is judged again as more of its decay comes in
+                _ => state,
+            },
+            state => state,
+        };
+        matches!(self.states[button], State::Passed { .. })
+    }
+
+    /// The envelope of `button`, oldest first, as (ms, dB)
+    fn envelope(&self, button: usize) -> impl Iterator<Item = (f32, f32)> + Clone + '_ {
+        let start = (self.pos + HISTORY - self.filled) % HISTORY;
+        (0..self.filled).map(move |i| {
+            let at = (start + i) % HISTORY;
+            (self.times[at] as f32 / 1000f32, self.levels[button][at])
+        })
+    }
+
+    /// Whether `button`'s envelope since its trigger started at `onset` is a strike,
+    /// or `None` until enough of the decay is in to tell
+    fn is_strike(&self, button: usize, onset: u64) -> Option<bool> {
+        let c = &self.criteria;
+        //the trigger starts on the attack, so the strike peaks within an attack's time of it
+        let onset_ms = onset as f32 / 1000f32;
+        let near_onset = |ms: f32| fabsf(ms - onset_ms) <= c.max_attack_ms as f32;
+        let (peak_at, (peak_ms, peak_db)) = match self.envelope(button).enumerate()
+            .filter(|(_, (ms, _))| near_onset(*ms))
+            .fold(None, |best: Option<(usize, (f32, f32))>, (i, p)| match best {
+                Some((_, b)) if b.1 >= p.1 => best,
+                _ => Some((i, p)),
+            }) {
+            Some(peak) => peak,
+            None => return Some(false),
+        };
+
+        //a sharp attack: well below the peak a moment before it
+        let attack = self.envelope(button)
+            .take(peak_at)
+            .filter(|(ms, _)| peak_ms - ms <= c.max_attack_ms as f32)
+            .any(|(_, db)| peak_db - db >= c.min_attack_db);
+        if !attack {
+            return Some(false);
+        }
+
+        //and out of the background just before it, not just out of one of the dips noise is full of
+        let mut background = [0f32; HISTORY];
+        let mut n = 0;
+        for (_, db) in self.envelope(button)
+            .take(peak_at)
+            .filter(|(ms, _)| peak_ms - ms > c.max_attack_ms as f32 && peak_ms - ms <= 2f32 * c.max_attack_ms as f32) {
+            background[n] = db;
+            n += 1;
+        }
+        if n == 0 {
+            return Some(false);
+        }
+        let background = &mut background[..n];
+        background.sort_unstable_by(|a, b| a.total_cmp(b));
+        if peak_db - background[n / 2] < c.min_attack_db {
+            return Some(false);
+        }
+
+        //then an exponential decay, which is a straight line in dB
+        let decay = self.envelope(button).skip(peak_at);
+        let n = self.filled - peak_at;
+        if n < c.min_decay_points + 1 {
+            return None;
+        }
+        let (sum_t, sum_db) = decay.clone().fold((0f32, 0f32), |(st, sd), (ms, db)| (st + ms - peak_ms, sd + db));
+        let (mean_t, mean_db) = (sum_t / n as f32, sum_db / n as f32);
+        let (cov, var) = decay.clone().fold((0f32, 0f32), |(cov, var), (ms, db)| {
+            let dt = ms - peak_ms - mean_t;
+            (cov + dt * (db - mean_db), var + dt * dt)
+        });
+        if var <= 0f32 {
+            return Some(false);
+        }
+        let slope = cov / var;
+        if slope >= 0f32 {
+            return Some(false);
+        }
+        let residual = decay.fold(0f32, |sum, (ms, db)| {
+            let err = db - mean_db - slope * (ms - peak_ms - mean_t);
+            sum + err * err
+        });
+        if sqrtf(residual / n as f32) > c.max_decay_residual_db {
+            return Some(false);
+        }
+        let tau_ms = DB_PER_TAU / -slope;
+        Some(tau_ms >= c.min_decay_ms && tau_ms <= c.max_decay_ms)
+    }
+}
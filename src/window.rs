@@ -0,0 +1,154 @@
+use libm::cosf;
+use crate::goertzel::PI;
+
+/// The window functions available for the Goertzel path
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WindowKind {
+    Rectangular,
+    Hann,
+    Hamming,
+    /// 4-term Blackman-Harris, for the lowest sidelobes
+    BlackmanHarris,
+    /// Flat-top, for the most accurate tone power between bins
+    FlatTop,
+}
+
+impl WindowKind {
+    /// The cosine-sum coefficients a0..a4 of the window
+    pub fn coefficients(&self) -> [f32; 5] {
+        match self {
+            WindowKind::Rectangular => [1.0, 0.0, 0.0, 0.0, 0.0],
+            WindowKind::Hann => [0.5, 0.5, 0.0, 0.0, 0.0],
+            WindowKind::Hamming => [0.54, 0.46, 0.0, 0.0, 0.0],
+            WindowKind::BlackmanHarris => [0.35875, 0.48829, 0.14128, 0.01168, 0.0],
+            WindowKind::FlatTop => [0.21557895, 0.41663158, 0.27726316, 0.08357895, 0.006947368],
+        }
+    }
+}
+
+/// A precomputed window of up to `N` samples, to be applied to a block of
+/// the same length before it goes through the Goertzel filters.
+///
+/// A window scales a tone's amplitude by its coherent gain, so window
+/// power is compensated by `1 / gain^2` to keep `power()` and `dbm()`
+/// readings comparable across window types.
+#[derive(Copy, Clone, Debug)]
+pub struct Window<const N: usize> {
+    kind: WindowKind,
+    /// The window coefficients
+    coef: [f32; N],
+    /// The window coefficients in Q15, for the fixed-point path
+    coef_q15: [i16; N],
+    /// How many of the `N` coefficients are in use
+    len: usize,
+    /// The coherent gain, sum(w) / len
+    gain: f32,
+    /// The equivalent noise bandwidth, in bins
+    enbw: f32,
+}
+
+impl<const N: usize> Window<N> {
+    /// Returns a periodic (DFT-even) window of the given kind
+    pub fn new(kind: WindowKind) -> Window<N> {
+        Window::with_len(kind, N)
+    }
+
+    /// Returns a periodic window of the given kind over only the first
+    /// `len` samples, for block lengths chosen at run time by `planner::plan`
+    pub fn with_len(kind: WindowKind, len: usize) -> Window<N> {
+        Window::build(kind, len, len)
+    }
+
+    /// Returns a symmetric (DFT-odd) window of the given kind, whose first
+    /// and last coefficients match, for designing linear-phase FIR filters
+    pub fn symmetric(kind: WindowKind) -> Window<N> {
+        Window::build(kind, N, (N - 1).max(1))
+    }
+
+    /// A window over `len` samples, with its cosines repeating every `period`
+    fn build(kind: WindowKind, len: usize, period: usize) -> Window<N> {
+        assert!(len > 0 && len <= N);
+        let a = kind.coefficients();
+        let mut coef = [0f32; N];
+        let mut coef_q15 = [0i16; N];
+        let mut sum = 0f32;
+        let mut sum_sq = 0f32;
+        for (n, (c, q)) in coef.iter_mut().zip(coef_q15.iter_mut()).take(len).enumerate() {
+            let x = 2.0 * PI * n as f32 / period as f32;
+            let w = a[0] - a[1] * cosf(x) + a[2] * cosf(2.0 * x)
+                - a[3] * cosf(3.0 * x) + a[4] * cosf(4.0 * x);
+            *c = w;
+            *q = libm::fminf(libm::roundf(w * 32768.0), i16::MAX as f32) as i16;
+            sum += w;
+            sum_sq += w * w;
+        }
+        Window {
+            kind,
+            coef,
+            coef_q15,
+            len,
+            gain: sum / len as f32,
+            enbw: len as f32 * sum_sq / (sum * sum),
+        }
+    }
+
+    pub fn kind(&self) -> WindowKind {
+        self.kind
+    }
+
+    /// How many samples the window is
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// True if the window has no samples
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The window coefficients
+    pub fn coefficients(&self) -> &[f32] {
+        &self.coef[..self.len]
+    }
+
+    /// The window coefficients in Q15
+    pub fn coefficients_q15(&self) -> &[i16] {
+        &self.coef_q15[..self.len]
+    }
+
+    /// The coherent gain, the factor a tone's amplitude is scaled by
+    pub fn coherent_gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// The equivalent noise bandwidth in bins, how much wider than a
+    /// rectangular window's bin the window lets broadband noise in
+    pub fn enbw(&self) -> f32 {
+        self.enbw
+    }
+
+    /// Applies the window to a block of samples in place
+    pub fn apply(&self, sample: &mut [f32]) {
+        assert_eq!(sample.len(), self.len);
+        for (x, w) in sample.iter_mut().zip(self.coefficients().iter()) {
+            *x *= w;
+        }
+    }
+
+    /// Applies the window to a block of Q15 samples into `out`, keeping
+    /// the product's 15 fractional bits rather than rounding them away, so
+    /// `out` is the windowed block scaled up by 2^15
+    pub fn apply_q15(&self, sample: &[i16], out: &mut [i32]) {
+        assert_eq!(sample.len(), self.len);
+        assert_eq!(out.len(), self.len);
+        for ((y, x), w) in out.iter_mut().zip(sample.iter()).zip(self.coefficients_q15().iter()) {
+            *y = *x as i32 * *w as i32;
+        }
+    }
+
+    /// Returns the power of a windowed tone on the same scale as it would
+    /// read through a rectangular window
+    pub fn compensate(&self, power: f32) -> f32 {
+        power / (self.gain * self.gain)
+    }
+}
@@ -0,0 +1,103 @@
+use libm::sqrtf;
+use crate::dtmf_signals::PowerSpectrum;
+use crate::goertzel::FilterBank;
+use crate::window::Window;
+
+/// A spectrum of `M` points spread evenly over just one band
+#[derive(Copy, Clone, Debug)]
+pub struct ZoomSpectrum<const M: usize> {
+    /// The frequency of each point, in Hz
+    freqs: [f32; M],
+    /// The magnitude at each point, scaled like an FFT spectrum with
+    /// `divide_by_N` so thresholds carry over between the two
+    values: [f32; M],
+}
+
+impl<const M: usize> ZoomSpectrum<M> {
+    /// The (frequency, magnitude) of each point
+    pub fn data(&self) -> impl Iterator<Item=(f32, f32)> + '_ {
+        self.freqs.iter().cloned().zip(self.values.iter().cloned())
+    }
+
+    /// The spacing between points, in Hz
+    pub fn resolution(&self) -> f32 {
+        if M > 1 { self.freqs[1] - self.freqs[0] } else { 0.0 }
+    }
+}
+
+impl<const M: usize> PowerSpectrum for ZoomSpectrum<M> {
+    fn for_each_bin(&self, f: &mut dyn FnMut(f32, f32)) {
+        for (fr, fr_val) in self.data() {
+            f(fr, fr_val);
+        }
+    }
+}
+
+/// Zooms in on one band, such as the remote's 37-42 kHz, instead of
+/// computing a full FFT and throwing most of it away.
+///
+/// Each of the `M` points is a Goertzel filter of the bank, so the band is
+/// sampled on a grid of `M` frequencies in one pass over the block at a
+/// cost of `M` multiplies per sample, rather than with a chirp-z
+/// transform's FFTs. That is cheap for the few dozen points a band needs,
+/// and the points can be spaced far closer than an FFT's bins of the same
+/// block, which makes peaks between bins easy to pick out.
+#[derive(Copy, Clone, Debug)]
+pub struct ZoomAnalyzer<const M: usize> {
+    bank: FilterBank<M>,
+}
+
+impl<const M: usize> ZoomAnalyzer<M> {
+    /// Returns an analyzer for `M` points from `start` to `stop` Hz
+    /// * `fs`  The sampling frequency of the samples to process
+    pub fn new(start: f32, stop: f32, fs: f32) -> ZoomAnalyzer<M> {
+        assert!(M > 1);
+        assert!(start < stop);
+        let step = (stop - start) / (M - 1) as f32;
+        let mut freqs = [0f32; M];
+        for (i, f) in freqs.iter_mut().enumerate() {
+            *f = start + i as f32 * step;
+        }
+        ZoomAnalyzer {
+            bank: FilterBank::new(&freqs, fs),
+        }
+    }
+
+    /// Retunes the analyzer to a measured sampling frequency, see
+    /// `FilterBank::track`. Returns true if it retuned
+    pub fn track(&mut self, fs: f32, tolerance: f32) -> bool {
+        self.bank.track(fs, tolerance)
+    }
+
+    /// Returns the zoomed spectrum of one block of samples
+    pub fn analyze(&self, sample: &[f32]) -> ZoomSpectrum<M> {
+        self.spectrum(self.bank.process(sample))
+    }
+
+    /// Returns the zoomed spectrum of one windowed block of samples
+    pub fn analyze_windowed<const N: usize>(&self, sample: &[f32], window: &Window<N>) -> ZoomSpectrum<M> {
+        self.spectrum(self.bank.process_windowed(sample, window))
+    }
+
+    /// Returns the zoomed spectrum of one block of Q15 samples
+    pub fn analyze_q15(&self, sample: &[i16]) -> ZoomSpectrum<M> {
+        self.spectrum(self.bank.process_q15(sample))
+    }
+
+    /// Returns the zoomed spectrum of one windowed block of Q15 samples
+    pub fn analyze_q15_windowed<const N: usize>(&self, sample: &[i16], window: &Window<N>) -> ZoomSpectrum<M> {
+        self.spectrum(self.bank.process_q15_windowed(sample, window))
+    }
+
+    fn spectrum(&self, powers: [f32; M]) -> ZoomSpectrum<M> {
+        let mut zoom = ZoomSpectrum {
+            freqs: [0f32; M],
+            values: [0f32; M],
+        };
+        for (i, ((f, v), p)) in zoom.freqs.iter_mut().zip(zoom.values.iter_mut()).zip(powers.iter()).enumerate() {
+            *f = self.bank.frequency(i);
+            *v = sqrtf(*p);
+        }
+        zoom
+    }
+}